@@ -3,3 +3,7 @@
 elm make src/pages/Login.elm --output=static/login.html $@
 elm make src/pages/Register.elm --output=static/register.html $@
 elm make src/pages/Activate.elm --output=static/activate.html $@
+elm make src/pages/Consent.elm --output=static/consent.html $@
+elm make src/pages/Device.elm --output=static/device.html $@
+elm make src/pages/Logout.elm --output=static/logout.html $@
+elm make src/pages/Grants.elm --output=static/grants.html $@
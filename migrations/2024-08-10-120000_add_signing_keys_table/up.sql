@@ -0,0 +1,18 @@
+-- Your SQL goes here
+CREATE TABLE signing_keys
+(
+    id           UUID PRIMARY KEY      DEFAULT gen_random_uuid(),
+    kid          VARCHAR(255) NOT NULL,
+    private_key  TEXT         NOT NULL,
+    activates_at TIMESTAMPTZ  NOT NULL DEFAULT NOW(),
+    retires_at   TIMESTAMPTZ,
+    updated_at   TIMESTAMPTZ  NOT NULL DEFAULT NOW(),
+    created_at   TIMESTAMPTZ  NOT NULL DEFAULT NOW(),
+    CONSTRAINT unique_kid UNIQUE (kid)
+);
+
+CREATE TRIGGER set_signing_keys_updated_at
+    BEFORE UPDATE
+    ON signing_keys
+    FOR EACH ROW
+EXECUTE FUNCTION set_updated_at();
@@ -0,0 +1,20 @@
+-- Your SQL goes here
+CREATE TABLE refresh_token_families
+(
+    id          UUID PRIMARY KEY      DEFAULT gen_random_uuid(),
+    client_id   VARCHAR(255) NOT NULL,
+    user_id     UUID         NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    scope       VARCHAR(1024),
+    current_jti UUID         NOT NULL,
+    auth_time   TIMESTAMPTZ  NOT NULL,
+    expires_at  TIMESTAMPTZ  NOT NULL,
+    revoked_at  TIMESTAMPTZ,
+    updated_at  TIMESTAMPTZ  NOT NULL DEFAULT NOW(),
+    created_at  TIMESTAMPTZ  NOT NULL DEFAULT NOW()
+);
+
+CREATE TRIGGER set_refresh_token_families_updated_at
+    BEFORE UPDATE
+    ON refresh_token_families
+    FOR EACH ROW
+EXECUTE FUNCTION set_updated_at();
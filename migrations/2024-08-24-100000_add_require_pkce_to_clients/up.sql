@@ -0,0 +1,3 @@
+-- Your SQL goes here
+ALTER TABLE clients
+    ADD COLUMN require_pkce BOOLEAN NOT NULL DEFAULT FALSE;
@@ -0,0 +1,22 @@
+-- Your SQL goes here
+CREATE TABLE client_scopes
+(
+    id         UUID PRIMARY KEY      DEFAULT gen_random_uuid(),
+    client_id  UUID         NOT NULL REFERENCES clients (id) ON DELETE CASCADE,
+    scope      VARCHAR(255) NOT NULL,
+    updated_at TIMESTAMPTZ  NOT NULL DEFAULT NOW(),
+    created_at TIMESTAMPTZ  NOT NULL DEFAULT NOW(),
+    UNIQUE (client_id, scope)
+);
+
+CREATE TRIGGER set_client_scopes_updated_at
+    BEFORE UPDATE
+    ON client_scopes
+    FOR EACH ROW
+EXECUTE FUNCTION set_updated_at();
+
+-- existing clients keep being able to request the scopes they implicitly had
+INSERT INTO client_scopes (client_id, scope)
+SELECT clients.id, scope
+FROM clients,
+     UNNEST(ARRAY ['openid', 'profile', 'email']) AS scope;
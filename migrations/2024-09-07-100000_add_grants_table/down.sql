@@ -0,0 +1,6 @@
+-- This file should undo anything in `up.sql`
+DROP TABLE grants;
+
+ALTER TABLE clients
+    DROP COLUMN name,
+    DROP COLUMN skip_consent;
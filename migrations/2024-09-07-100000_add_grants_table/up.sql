@@ -0,0 +1,21 @@
+-- Your SQL goes here
+ALTER TABLE clients
+    ADD COLUMN name         VARCHAR(255),
+    ADD COLUMN skip_consent BOOLEAN NOT NULL DEFAULT FALSE;
+
+CREATE TABLE grants
+(
+    id         UUID PRIMARY KEY       DEFAULT gen_random_uuid(),
+    user_id    UUID          NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    client_id  UUID          NOT NULL REFERENCES clients (id) ON DELETE CASCADE,
+    scope      VARCHAR(1024) NOT NULL,
+    updated_at TIMESTAMPTZ   NOT NULL DEFAULT NOW(),
+    created_at TIMESTAMPTZ   NOT NULL DEFAULT NOW(),
+    UNIQUE (user_id, client_id)
+);
+
+CREATE TRIGGER set_grants_updated_at
+    BEFORE UPDATE
+    ON grants
+    FOR EACH ROW
+EXECUTE FUNCTION set_updated_at();
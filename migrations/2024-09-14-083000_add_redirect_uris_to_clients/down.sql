@@ -0,0 +1,10 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE clients
+    ADD COLUMN redirect_uri VARCHAR(255) NOT NULL DEFAULT '';
+
+UPDATE clients
+SET redirect_uri = COALESCE(redirect_uris[1], '');
+
+ALTER TABLE clients
+    ALTER COLUMN redirect_uri DROP DEFAULT,
+    DROP COLUMN redirect_uris;
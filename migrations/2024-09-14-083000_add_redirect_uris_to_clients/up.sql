@@ -0,0 +1,9 @@
+-- Your SQL goes here
+ALTER TABLE clients
+    ADD COLUMN redirect_uris TEXT[] NOT NULL DEFAULT '{}';
+
+UPDATE clients
+SET redirect_uris = ARRAY [redirect_uri];
+
+ALTER TABLE clients
+    DROP COLUMN redirect_uri;
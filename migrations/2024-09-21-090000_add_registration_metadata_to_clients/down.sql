@@ -0,0 +1,8 @@
+-- This file should undo anything in `up.sql`
+DROP INDEX clients_client_id_key;
+
+ALTER TABLE clients
+    DROP COLUMN grant_types,
+    DROP COLUMN token_endpoint_auth_method,
+    DROP COLUMN logo_uri,
+    DROP COLUMN registration_access_token;
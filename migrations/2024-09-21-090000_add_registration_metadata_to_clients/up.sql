@@ -0,0 +1,12 @@
+-- Your SQL goes here
+ALTER TABLE clients
+    ADD COLUMN grant_types                TEXT[]       NOT NULL DEFAULT '{authorization_code,refresh_token}',
+    ADD COLUMN token_endpoint_auth_method VARCHAR(255) NOT NULL DEFAULT 'client_secret_post',
+    ADD COLUMN logo_uri                   VARCHAR(1024),
+    ADD COLUMN registration_access_token  VARCHAR(255);
+
+-- existing clients keep being able to use every grant type
+UPDATE clients
+SET grant_types = ARRAY ['authorization_code', 'refresh_token', 'client_credentials', 'urn:ietf:params:oauth:grant-type:device_code'];
+
+CREATE UNIQUE INDEX clients_client_id_key ON clients (client_id);
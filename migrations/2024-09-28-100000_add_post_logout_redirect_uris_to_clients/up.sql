@@ -0,0 +1,3 @@
+-- Your SQL goes here
+ALTER TABLE clients
+    ADD COLUMN post_logout_redirect_uris TEXT[] NOT NULL DEFAULT '{}';
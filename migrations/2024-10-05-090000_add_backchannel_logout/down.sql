@@ -0,0 +1,6 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE refresh_token_families
+    DROP COLUMN sid;
+
+ALTER TABLE clients
+    DROP COLUMN backchannel_logout_uri;
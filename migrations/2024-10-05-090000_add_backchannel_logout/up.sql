@@ -0,0 +1,6 @@
+-- Your SQL goes here
+ALTER TABLE clients
+    ADD COLUMN backchannel_logout_uri VARCHAR(1024);
+
+ALTER TABLE refresh_token_families
+    ADD COLUMN sid VARCHAR(255);
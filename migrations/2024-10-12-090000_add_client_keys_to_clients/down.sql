@@ -0,0 +1,12 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE clients
+    DROP COLUMN jwks_uri,
+    DROP COLUMN jwks;
+
+-- clients without a secret can no longer authenticate
+UPDATE clients
+SET client_secret = ''
+WHERE client_secret IS NULL;
+
+ALTER TABLE clients
+    ALTER COLUMN client_secret SET NOT NULL;
@@ -0,0 +1,5 @@
+-- Your SQL goes here
+ALTER TABLE clients
+    ALTER COLUMN client_secret DROP NOT NULL,
+    ADD COLUMN jwks JSONB,
+    ADD COLUMN jwks_uri VARCHAR(1024);
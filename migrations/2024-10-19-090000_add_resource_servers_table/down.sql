@@ -0,0 +1,5 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE refresh_token_families
+    DROP COLUMN resource;
+
+DROP TABLE resource_servers;
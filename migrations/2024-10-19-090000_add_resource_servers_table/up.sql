@@ -0,0 +1,19 @@
+-- Your SQL goes here
+CREATE TABLE resource_servers
+(
+    id         UUID PRIMARY KEY       DEFAULT gen_random_uuid(),
+    identifier VARCHAR(1024) NOT NULL,
+    name       VARCHAR(255),
+    updated_at TIMESTAMPTZ   NOT NULL DEFAULT NOW(),
+    created_at TIMESTAMPTZ   NOT NULL DEFAULT NOW(),
+    CONSTRAINT unique_identifier UNIQUE (identifier)
+);
+
+CREATE TRIGGER set_resource_servers_updated_at
+    BEFORE UPDATE
+    ON resource_servers
+    FOR EACH ROW
+EXECUTE FUNCTION set_updated_at();
+
+ALTER TABLE refresh_token_families
+    ADD COLUMN resource VARCHAR(1024);
@@ -0,0 +1,18 @@
+-- Your SQL goes here
+CREATE TABLE token_exchange_policies
+(
+    id                UUID PRIMARY KEY       DEFAULT gen_random_uuid(),
+    client_id         UUID          NOT NULL REFERENCES clients (id) ON DELETE CASCADE,
+    subject_client_id VARCHAR(255)  NOT NULL,
+    audience          VARCHAR(1024),
+    updated_at        TIMESTAMPTZ   NOT NULL DEFAULT NOW(),
+    created_at        TIMESTAMPTZ   NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX token_exchange_policies_client_id_idx ON token_exchange_policies (client_id);
+
+CREATE TRIGGER set_token_exchange_policies_updated_at
+    BEFORE UPDATE
+    ON token_exchange_policies
+    FOR EACH ROW
+EXECUTE FUNCTION set_updated_at();
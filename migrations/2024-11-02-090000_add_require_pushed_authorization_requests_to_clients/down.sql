@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE clients
+    DROP COLUMN require_pushed_authorization_requests;
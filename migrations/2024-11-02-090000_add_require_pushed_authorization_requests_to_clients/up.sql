@@ -0,0 +1,3 @@
+-- Your SQL goes here
+ALTER TABLE clients
+    ADD COLUMN require_pushed_authorization_requests BOOLEAN NOT NULL DEFAULT FALSE;
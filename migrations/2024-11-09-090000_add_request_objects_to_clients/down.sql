@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE clients
+    DROP COLUMN request_uris,
+    DROP COLUMN require_signed_request_object;
@@ -0,0 +1,4 @@
+-- Your SQL goes here
+ALTER TABLE clients
+    ADD COLUMN request_uris TEXT[] NOT NULL DEFAULT '{}',
+    ADD COLUMN require_signed_request_object BOOLEAN NOT NULL DEFAULT FALSE;
@@ -0,0 +1,45 @@
+use diesel::sql_types::{Nullable, Text};
+use diesel::RunQueryDsl;
+use std::env;
+use url::Url;
+
+/// Lets a client exchange tokens issued to another client (RFC 8693), for tokens of the given
+/// audience or of any audience when it is left out.
+fn main() {
+    dotenv::from_filename(".env.local").ok();
+
+    let usage = "usage: allow-token-exchange <client id> <subject client id> [audience]";
+    let (client_id, subject_client_id) = env::args().nth(1).zip(env::args().nth(2)).expect(usage);
+    // audiences are resource indicators, absolute URIs without a fragment (RFC 8707 section 2)
+    let audience = env::args().nth(3);
+    if audience
+        .as_deref()
+        .is_some_and(|audience| !Url::parse(audience).is_ok_and(|url| url.fragment().is_none()))
+    {
+        panic!("{}", usage);
+    }
+
+    let url = env::var("POSTGRES_URL").expect("POSTGRES_URL must be set");
+    let mut connection: diesel::PgConnection = diesel::connection::Connection::establish(&url)
+        .expect("Failed to establish a database connection");
+
+    let inserted = diesel::sql_query(
+        "INSERT INTO token_exchange_policies (client_id, subject_client_id, audience) \
+         SELECT id, $2, $3 FROM clients WHERE client_id = $1",
+    )
+    .bind::<Text, _>(&client_id)
+    .bind::<Text, _>(&subject_client_id)
+    .bind::<Nullable<Text>, _>(&audience)
+    .execute(&mut connection)
+    .expect("failed to save token exchange policy");
+    if inserted == 0 {
+        panic!("client {} not found", client_id);
+    }
+
+    println!(
+        "{} may exchange tokens of {} for {}",
+        client_id,
+        subject_client_id,
+        audience.as_deref().unwrap_or("any audience")
+    );
+}
@@ -0,0 +1,33 @@
+use diesel::sql_types::{Nullable, Text};
+use diesel::RunQueryDsl;
+use std::env;
+use url::Url;
+
+/// Registers a resource server access tokens can be issued for, or renames it when it is
+/// already registered.
+fn main() {
+    dotenv::from_filename(".env.local").ok();
+
+    let usage = "usage: register-resource-server <resource indicator> [name]";
+    // resource indicators must be absolute URIs without a fragment (RFC 8707 section 2)
+    let identifier = env::args()
+        .nth(1)
+        .filter(|identifier| Url::parse(identifier).is_ok_and(|url| url.fragment().is_none()))
+        .expect(usage);
+    let name = env::args().nth(2);
+
+    let url = env::var("POSTGRES_URL").expect("POSTGRES_URL must be set");
+    let mut connection: diesel::PgConnection = diesel::connection::Connection::establish(&url)
+        .expect("Failed to establish a database connection");
+
+    diesel::sql_query(
+        "INSERT INTO resource_servers (identifier, name) VALUES ($1, $2) \
+         ON CONFLICT (identifier) DO UPDATE SET name = EXCLUDED.name",
+    )
+    .bind::<Text, _>(&identifier)
+    .bind::<Nullable<Text>, _>(&name)
+    .execute(&mut connection)
+    .expect("failed to register resource server");
+
+    println!("resource server {} registered", identifier);
+}
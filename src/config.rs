@@ -4,8 +4,19 @@ pub struct Config {
     pub port: u16,
     pub postgres_url: String,
     pub redis_url: String,
-    pub jwt_secret: String,
+    pub jwt_secret: Option<String>,
+    pub jwt_signing_key: Option<String>,
+    pub jwt_signing_key_id: Option<String>,
+    pub jwt_signing_algorithm: jsonwebtoken::Algorithm,
+    pub jwt_key_rotation_interval: Option<chrono::Duration>,
+    pub jwt_key_prepublish: chrono::Duration,
+    pub jwt_key_retention: chrono::Duration,
     pub base_url: String,
+    pub client_registration_token: Option<String>,
+    pub admin_token: Option<String>,
+    pub session_secret: Option<String>,
+    pub session_idle_timeout: chrono::Duration,
+    pub session_absolute_timeout: chrono::Duration,
     pub smtp_host: String,
     pub smtp_username: String,
     pub smtp_password: String,
@@ -22,8 +33,53 @@ impl Config {
                 .expect("SERVER_PORT must be a number"),
             postgres_url: env::var("POSTGRES_URL").expect("POSTGRES_URL must be set"),
             redis_url: env::var("REDIS_URL").expect("REDIS_URL must be set"),
-            jwt_secret: env::var("JWT_SECRET").expect("JWT_SECRET must be set"),
+            jwt_secret: env::var("JWT_SECRET").ok(),
+            jwt_signing_key: env::var("JWT_SIGNING_KEY").ok(),
+            jwt_signing_key_id: env::var("JWT_SIGNING_KEY_ID").ok(),
+            jwt_signing_algorithm: env::var("JWT_SIGNING_ALGORITHM")
+                .unwrap_or("RS256".to_string())
+                .parse()
+                .ok()
+                // only algorithms new signing keys can be generated for
+                .filter(|algorithm| {
+                    use jsonwebtoken::Algorithm::*;
+                    matches!(algorithm, RS256 | ES256 | EdDSA)
+                })
+                .expect("JWT_SIGNING_ALGORITHM must be RS256, ES256 or EdDSA"),
+            jwt_key_rotation_interval: env::var("JWT_KEY_ROTATION_DAYS").ok().map(|days| {
+                chrono::Duration::days(
+                    days.parse()
+                        .expect("JWT_KEY_ROTATION_DAYS must be a number"),
+                )
+            }),
+            jwt_key_prepublish: chrono::Duration::hours(
+                env::var("JWT_KEY_PREPUBLISH_HOURS")
+                    .unwrap_or("24".to_string())
+                    .parse()
+                    .expect("JWT_KEY_PREPUBLISH_HOURS must be a number"),
+            ),
+            jwt_key_retention: chrono::Duration::days(
+                env::var("JWT_KEY_RETENTION_DAYS")
+                    .unwrap_or("30".to_string())
+                    .parse()
+                    .expect("JWT_KEY_RETENTION_DAYS must be a number"),
+            ),
             base_url: env::var("BASE_URL").expect("BASE_URL must be set"),
+            client_registration_token: env::var("CLIENT_REGISTRATION_TOKEN").ok(),
+            admin_token: env::var("ADMIN_TOKEN").ok(),
+            session_secret: env::var("SESSION_SECRET").ok(),
+            session_idle_timeout: chrono::Duration::minutes(
+                env::var("SESSION_IDLE_TIMEOUT_MINUTES")
+                    .unwrap_or("120".to_string())
+                    .parse()
+                    .expect("SESSION_IDLE_TIMEOUT_MINUTES must be a number"),
+            ),
+            session_absolute_timeout: chrono::Duration::hours(
+                env::var("SESSION_ABSOLUTE_TIMEOUT_HOURS")
+                    .unwrap_or("24".to_string())
+                    .parse()
+                    .expect("SESSION_ABSOLUTE_TIMEOUT_HOURS must be a number"),
+            ),
             smtp_host: env::var("SMTP_HOST").expect("SMTP_HOST must be set"),
             smtp_username: env::var("SMTP_USERNAME").expect("SMTP_USERNAME must be set"),
             smtp_password: env::var("SMTP_PASSWORD").expect("SMTP_PASSWORD must be set"),
@@ -1,16 +1,117 @@
 // @generated automatically by Diesel CLI.
 
+diesel::table! {
+    client_scopes (id) {
+        id -> Uuid,
+        client_id -> Uuid,
+        #[max_length = 255]
+        scope -> Varchar,
+        updated_at -> Timestamptz,
+        created_at -> Timestamptz,
+    }
+}
+
 diesel::table! {
     clients (id) {
         id -> Uuid,
         #[max_length = 255]
         client_id -> Varchar,
         #[max_length = 255]
-        client_secret -> Varchar,
-        #[max_length = 255]
-        redirect_uri -> Varchar,
+        client_secret -> Nullable<Varchar>,
         created_at -> Nullable<Timestamp>,
         updated_at -> Nullable<Timestamp>,
+        require_pkce -> Bool,
+        #[max_length = 255]
+        name -> Nullable<Varchar>,
+        skip_consent -> Bool,
+        redirect_uris -> Array<Nullable<Text>>,
+        grant_types -> Array<Nullable<Text>>,
+        #[max_length = 255]
+        token_endpoint_auth_method -> Varchar,
+        #[max_length = 1024]
+        logo_uri -> Nullable<Varchar>,
+        #[max_length = 255]
+        registration_access_token -> Nullable<Varchar>,
+        post_logout_redirect_uris -> Array<Nullable<Text>>,
+        #[max_length = 1024]
+        backchannel_logout_uri -> Nullable<Varchar>,
+        jwks -> Nullable<Jsonb>,
+        #[max_length = 1024]
+        jwks_uri -> Nullable<Varchar>,
+        require_pushed_authorization_requests -> Bool,
+        request_uris -> Array<Nullable<Text>>,
+        require_signed_request_object -> Bool,
+    }
+}
+
+diesel::table! {
+    grants (id) {
+        id -> Uuid,
+        user_id -> Uuid,
+        client_id -> Uuid,
+        #[max_length = 1024]
+        scope -> Varchar,
+        updated_at -> Timestamptz,
+        created_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    refresh_token_families (id) {
+        id -> Uuid,
+        #[max_length = 255]
+        client_id -> Varchar,
+        user_id -> Uuid,
+        #[max_length = 1024]
+        scope -> Nullable<Varchar>,
+        current_jti -> Uuid,
+        auth_time -> Timestamptz,
+        expires_at -> Timestamptz,
+        revoked_at -> Nullable<Timestamptz>,
+        updated_at -> Timestamptz,
+        created_at -> Timestamptz,
+        #[max_length = 255]
+        sid -> Nullable<Varchar>,
+        #[max_length = 1024]
+        resource -> Nullable<Varchar>,
+    }
+}
+
+diesel::table! {
+    resource_servers (id) {
+        id -> Uuid,
+        #[max_length = 1024]
+        identifier -> Varchar,
+        #[max_length = 255]
+        name -> Nullable<Varchar>,
+        updated_at -> Timestamptz,
+        created_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    signing_keys (id) {
+        id -> Uuid,
+        #[max_length = 255]
+        kid -> Varchar,
+        private_key -> Text,
+        activates_at -> Timestamptz,
+        retires_at -> Nullable<Timestamptz>,
+        updated_at -> Timestamptz,
+        created_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    token_exchange_policies (id) {
+        id -> Uuid,
+        client_id -> Uuid,
+        #[max_length = 255]
+        subject_client_id -> Varchar,
+        #[max_length = 1024]
+        audience -> Nullable<Varchar>,
+        updated_at -> Timestamptz,
+        created_at -> Timestamptz,
     }
 }
 
@@ -29,7 +130,19 @@ diesel::table! {
     }
 }
 
+diesel::joinable!(client_scopes -> clients (client_id));
+diesel::joinable!(grants -> clients (client_id));
+diesel::joinable!(grants -> users (user_id));
+diesel::joinable!(refresh_token_families -> users (user_id));
+diesel::joinable!(token_exchange_policies -> clients (client_id));
+
 diesel::allow_tables_to_appear_in_same_query!(
+    client_scopes,
     clients,
+    grants,
+    refresh_token_families,
+    resource_servers,
+    signing_keys,
+    token_exchange_policies,
     users,
 );
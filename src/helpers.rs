@@ -1,10 +1,14 @@
 use crate::db::DbPoolError;
 use crate::kvs::KvsPoolError;
+use crate::services::tokens::keys::KeyError;
+use argon2::password_hash::rand_core::{OsRng, RngCore};
 use async_trait::async_trait;
 use axum::extract::{FromRequest, FromRequestParts, Request};
 use axum::http::request::Parts;
 use axum::http::StatusCode;
 use axum::response::{IntoResponse, Response};
+use base64::engine::general_purpose::{STANDARD, URL_SAFE_NO_PAD};
+use base64::Engine;
 use std::ops::Deref;
 
 pub trait Validatable {
@@ -104,6 +108,12 @@ pub enum InternalError {
     #[error("jwt error: {0}")]
     Jwt(#[from] jsonwebtoken::errors::Error),
 
+    #[error("signing key error: {0}")]
+    SigningKey(#[from] KeyError),
+
+    #[error("no active signing key")]
+    NoSigningKey,
+
     #[error("kvs pool error: {0}")]
     R2d2(#[from] KvsPoolError),
 
@@ -130,6 +140,13 @@ impl IntoResponse for InternalError {
     }
 }
 
+/// Unguessable url-safe identifier, for handles that are only kept server-side.
+pub fn random_token() -> String {
+    let mut token = [0u8; 32];
+    OsRng.fill_bytes(&mut token);
+    URL_SAFE_NO_PAD.encode(token)
+}
+
 pub struct TokenHeader(String);
 
 #[async_trait]
@@ -151,6 +168,7 @@ impl<S> FromRequestParts<S> for TokenHeader {
 }
 
 impl TokenHeader {
+    #[allow(clippy::result_large_err)]
     pub fn to_bearer_token(&self) -> Result<&str, Response> {
         if !self.0.starts_with("Bearer ") {
             return Err((StatusCode::BAD_REQUEST, "invalid Authorization header").into_response());
@@ -158,4 +176,69 @@ impl TokenHeader {
 
         Ok(&self.0[7..])
     }
+
+    pub fn is_basic(&self) -> bool {
+        self.0.starts_with("Basic ")
+    }
+
+    /// User id and password of `Basic` authentication (RFC 7617). Both are form-urlencoded
+    /// before encoding, as RFC 6749 section 2.3.1 requires for client credentials.
+    #[allow(clippy::result_large_err)]
+    pub fn to_basic_credentials(&self) -> Result<(String, String), Response> {
+        let invalid = || (StatusCode::BAD_REQUEST, "invalid Authorization header").into_response();
+        let decoded = self
+            .0
+            .strip_prefix("Basic ")
+            .and_then(|credentials| STANDARD.decode(credentials.trim()).ok())
+            .and_then(|credentials| String::from_utf8(credentials).ok())
+            .ok_or_else(invalid)?;
+        let (user_id, password) = decoded.split_once(':').ok_or_else(invalid)?;
+
+        let form_decode = |value: &str| {
+            percent_encoding::percent_decode_str(&value.replace('+', " "))
+                .decode_utf8()
+                .map(|value| value.into_owned())
+                .map_err(|_| invalid())
+        };
+
+        Ok((form_decode(user_id)?, form_decode(password)?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn basic(credentials: &str) -> TokenHeader {
+        TokenHeader(format!("Basic {}", STANDARD.encode(credentials)))
+    }
+
+    #[test]
+    fn basic_credentials_are_form_decoded() {
+        let (user_id, password) = basic("my%3Aclient:p%40ss+word%2B")
+            .to_basic_credentials()
+            .unwrap();
+
+        assert_eq!(user_id, "my:client");
+        assert_eq!(password, "p@ss word+");
+    }
+
+    #[test]
+    fn basic_password_may_contain_colons() {
+        let (user_id, password) = basic("client:se:cret").to_basic_credentials().unwrap();
+
+        assert_eq!(user_id, "client");
+        assert_eq!(password, "se:cret");
+    }
+
+    #[test]
+    fn malformed_basic_credentials_are_rejected() {
+        assert!(basic("no separator").to_basic_credentials().is_err());
+        assert!(TokenHeader("Basic !!!".to_string())
+            .to_basic_credentials()
+            .is_err());
+        assert!(TokenHeader("Bearer token".to_string())
+            .to_basic_credentials()
+            .is_err());
+    }
 }
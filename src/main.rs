@@ -8,14 +8,28 @@ mod services;
 use crate::config::Config;
 use crate::db::database_pool;
 use crate::kvs::kvs_pool;
+use crate::services::backchannel_logout::BackchannelLogoutService;
 use crate::services::clients::ClientService;
+use crate::services::devices::DeviceService;
+use crate::services::discovery::DiscoveryService;
 use crate::services::email::EmailService;
+use crate::services::grants::GrantService;
+use crate::services::pushed_authorizations::PushedAuthorizationService;
 use crate::services::rate_limit::RateLimitService;
-use crate::services::tokens::{JwtSecret, TokenService};
+use crate::services::refresh_tokens::RefreshTokenService;
+use crate::services::resource_servers::ResourceServerService;
+use crate::services::sessions::SessionService;
+use crate::services::signing_keys::SigningKeyService;
+use crate::services::token_exchange_policies::TokenExchangePolicyService;
+use crate::services::tokens::jwt::JwtSigner;
+use crate::services::tokens::keys::PrivateKey;
+use crate::services::tokens::TokenService;
 use crate::services::users::UserService;
-use axum::routing::{get, post};
+use axum::routing::{delete, get, post};
 use axum::Router;
+use axum_extra::extract::cookie::Key;
 use services::oauth2::Oauth2Service;
+use sha2::{Digest, Sha512};
 use std::sync::Arc;
 use tower::util::ServiceExt;
 use tower_http::services::ServeFile;
@@ -29,6 +43,12 @@ struct Services {
     oauth2_service: Arc<Oauth2Service>,
     email_service: Arc<EmailService>,
     rate_limit_service: Arc<RateLimitService>,
+    discovery_service: Arc<DiscoveryService>,
+    grant_service: Arc<GrantService>,
+    device_service: Arc<DeviceService>,
+    session_service: Arc<SessionService>,
+    backchannel_logout_service: Arc<BackchannelLogoutService>,
+    pushed_authorization_service: Arc<PushedAuthorizationService>,
 }
 
 #[tokio::main]
@@ -52,11 +72,61 @@ async fn main() -> Result<(), std::io::Error> {
         Arc::new(kvs_pool(&config.redis_url).expect("Failed to create KVS connection pool"));
 
     let user_service = Arc::new(UserService::new(db_pool.clone()));
-    let client_service = Arc::new(ClientService::new(db_pool.clone()));
-    let token_service = Arc::new(TokenService::new(
+    let resource_server_service = Arc::new(ResourceServerService::new(db_pool.clone()));
+    let client_service = Arc::new(
+        ClientService::new(
+            db_pool.clone(),
+            format!("{}/oauth2/register", config.base_url.trim_end_matches('/')),
+        )
+        .set_initial_access_token(config.client_registration_token.clone()),
+    );
+    let token_exchange_policy_service = Arc::new(TokenExchangePolicyService::new(db_pool.clone()));
+    let token_service = TokenService::new(
         kvs_pool.clone(),
-        JwtSecret(config.jwt_secret.as_ref()),
-    ));
+        config.base_url.trim_end_matches('/').to_string(),
+    );
+    let token_service = Arc::new(match &config.jwt_secret {
+        Some(secret) => token_service.set_legacy_signer(JwtSigner::new(secret.as_bytes())),
+        None => token_service,
+    });
+    let signing_key_service = Arc::new(
+        SigningKeyService::new(
+            db_pool.clone(),
+            token_service.clone(),
+            config.jwt_signing_algorithm,
+        )
+        .set_rotation(
+            config.jwt_key_rotation_interval,
+            config.jwt_key_prepublish,
+            config.jwt_key_retention,
+        ),
+    );
+    let initial_key = config.jwt_signing_key.as_ref().map(|path| {
+        let (key, kid) = PrivateKey::read_file(path).expect("failed to read JWT signing key");
+        (key, config.jwt_signing_key_id.clone().or(kid))
+    });
+    signing_key_service
+        .bootstrap(initial_key)
+        .await
+        .expect("failed to load signing keys");
+
+    if std::env::args().nth(1).as_deref() == Some("rotate-signing-key") {
+        // other instances pick the new key up within a minute, so it is published ahead of use
+        // unless the current key must go right away
+        let activates_at = match std::env::args().nth(2).as_deref() {
+            None => chrono::Utc::now() + config.jwt_key_prepublish,
+            Some("--now") => chrono::Utc::now(),
+            Some(_) => panic!("usage: rotate-signing-key [--now]"),
+        };
+        let kid = signing_key_service
+            .rotate(activates_at)
+            .await
+            .expect("failed to rotate signing key");
+        tracing::info!(kid, "signing key rotated");
+        return Ok(());
+    }
+    tokio::spawn(signing_key_service.run());
+
     let email_service = Arc::new(
         EmailService::new(
             format!("{}/activate", config.base_url)
@@ -73,10 +143,40 @@ async fn main() -> Result<(), std::io::Error> {
         .set_sender_name(config.smtp_sender_name.clone()),
     );
     let rate_limit_service = Arc::new(RateLimitService::new(kvs_pool.clone()));
+    let refresh_token_service = Arc::new(RefreshTokenService::new(db_pool.clone()));
+    let device_service = Arc::new(DeviceService::new(kvs_pool.clone()));
     let oauth2_service = Arc::new(Oauth2Service::new(
         token_service.clone(),
         client_service.clone(),
+        user_service.clone(),
+        refresh_token_service,
+        device_service.clone(),
+        resource_server_service,
+        token_exchange_policy_service,
     ));
+    let discovery_service = Arc::new(DiscoveryService::new(token_service.clone()));
+    let grant_service = Arc::new(GrantService::new(db_pool.clone(), kvs_pool.clone()));
+    let cookie_key = match &config.session_secret {
+        Some(secret) => Key::from(&Sha512::digest(secret.as_bytes())),
+        None => {
+            tracing::warn!("SESSION_SECRET is not set, sessions will not survive a restart");
+            Key::generate()
+        }
+    };
+    let session_service = Arc::new(
+        SessionService::new(
+            kvs_pool.clone(),
+            cookie_key,
+            config.base_url.starts_with("https://"),
+        )
+        .set_timeouts(config.session_idle_timeout, config.session_absolute_timeout)
+        .set_admin_token(config.admin_token.clone()),
+    );
+    let backchannel_logout_service = Arc::new(BackchannelLogoutService::new(
+        token_service.clone(),
+        client_service.clone(),
+    ));
+    let pushed_authorization_service = Arc::new(PushedAuthorizationService::new(kvs_pool.clone()));
 
     let services = Arc::new(Services {
         user_service,
@@ -85,6 +185,12 @@ async fn main() -> Result<(), std::io::Error> {
         token_service,
         email_service,
         rate_limit_service,
+        discovery_service,
+        grant_service,
+        device_service,
+        session_service,
+        backchannel_logout_service,
+        pushed_authorization_service,
     });
 
     let app = Router::new()
@@ -94,15 +200,71 @@ async fn main() -> Result<(), std::io::Error> {
         )
         .route(
             "/oauth2/login",
-            get(|req| ServeFile::new("static/login.html").oneshot(req)).post(routes::login),
+            get(routes::authorization).post(routes::login),
+        )
+        .route(
+            "/oauth2/consent",
+            get(|req| ServeFile::new("static/consent.html").oneshot(req))
+                .post(routes::consent::consent),
+        )
+        .route(
+            "/oauth2/consent/request",
+            get(routes::consent::consent_request),
+        )
+        .route(
+            "/oauth2/logout",
+            get(routes::logout::logout).post(routes::logout::confirm),
         )
         .route("/oauth2/token", post(routes::token))
+        .route("/oauth2/par", post(routes::pushed_authorization_request))
+        .route("/oauth2/register", post(routes::registration::register))
+        .route(
+            "/oauth2/register/:client_id",
+            get(routes::registration::read)
+                .put(routes::registration::update)
+                .delete(routes::registration::delete),
+        )
+        .route(
+            "/oauth2/device_authorization",
+            post(routes::device::device_authorization),
+        )
+        .route(
+            "/device",
+            get(|req| ServeFile::new("static/device.html").oneshot(req))
+                .post(routes::device::verify),
+        )
+        .route(
+            "/device/login",
+            get(|req| ServeFile::new("static/login.html").oneshot(req)).post(routes::device::login),
+        )
+        .route("/device/request", get(routes::device::device_request))
+        .route("/oauth2/introspect", post(routes::introspect))
+        .route("/oauth2/revoke", post(routes::revoke))
+        .route(
+            "/oauth2/userinfo",
+            get(routes::userinfo).post(routes::userinfo_form),
+        )
         .route(
             "/activate",
             get(|req| ServeFile::new("static/activate.html").oneshot(req)).post(routes::activate),
         )
         .route("/send-activation", post(routes::send_activation_email))
         .route("/profile", get(routes::profile))
+        .route(
+            "/grants",
+            get(|req| ServeFile::new("static/grants.html").oneshot(req)),
+        )
+        .route("/grants/list", get(routes::grants::list))
+        .route("/grants/revoke", post(routes::grants::revoke))
+        .route(
+            "/admin/users/:user_id/sessions",
+            delete(routes::admin::end_sessions),
+        )
+        .route(
+            "/.well-known/openid-configuration",
+            get(routes::well_known::openid_configuration),
+        )
+        .route("/.well-known/jwks.json", get(routes::well_known::jwks))
         .with_state(services)
         .layer(
             TraceLayer::new_for_http()
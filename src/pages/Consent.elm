@@ -0,0 +1,145 @@
+module Consent exposing (main)
+
+import Browser exposing (Document)
+import Css exposing (..)
+import Html.Styled exposing (Html, button, div, form, input, li, text, toUnstyled, ul)
+import Html.Styled.Attributes exposing (css, method, name, type_, value)
+import Http
+import Json.Decode as Decode
+import Layout exposing (mainPage)
+import Loader exposing (loader)
+import Scope exposing (scopeDescription)
+import Url exposing (Url)
+import Url.Parser exposing (parse, query)
+import Url.Parser.Query as Query
+
+
+type alias ConsentDetails =
+    { clientName : String
+    , scopes : List String
+    }
+
+
+type alias Model =
+    { challenge : String
+    , details : Maybe ConsentDetails
+    , error : Maybe String
+    }
+
+
+type Msg
+    = Noop
+    | GotDetails (Result Http.Error ConsentDetails)
+
+
+consentForm : Model -> ConsentDetails -> Html Msg
+consentForm model details =
+    form
+        [ method "post"
+        , css
+            [ displayFlex
+            , flexDirection column
+            , border2 (px 1) solid
+            , borderRadius (px 10)
+            , padding (px 20)
+            ]
+        ]
+        [ div [] [ text <| details.clientName ++ " would like to:" ]
+        , ul [] <|
+            case details.scopes of
+                [] ->
+                    [ li [] [ text "Access your account" ] ]
+
+                scopes ->
+                    List.map (\scope -> li [] [ text <| scopeDescription scope ]) scopes
+        , input [ name "challenge", type_ "hidden", value model.challenge ] []
+        , div [ css [ displayFlex, flexDirection row ] ]
+            [ div [ css [ flexGrow (num 1) ] ] []
+            , button
+                [ name "decision"
+                , value "deny"
+                , css [ minWidth (px 100), marginRight (em 1) ]
+                ]
+                [ text "Deny" ]
+            , button
+                [ name "decision"
+                , value "approve"
+                , css [ minWidth (px 100) ]
+                ]
+                [ text "Allow" ]
+            , div [ css [ flexGrow (num 1) ] ] []
+            ]
+        ]
+
+
+view : Model -> Document Msg
+view model =
+    { title = "Authorize application"
+    , body =
+        [ toUnstyled <|
+            mainPage <|
+                case ( model.details, model.error ) of
+                    ( _, Just error ) ->
+                        [ div [ css [ color (rgb 255 0 0), textAlign center ] ] [ text error ] ]
+
+                    ( Just details, Nothing ) ->
+                        [ consentForm model details ]
+
+                    ( Nothing, Nothing ) ->
+                        [ div [ css [ displayFlex, justifyContent center ] ] [ loader 100 ] ]
+        ]
+    }
+
+
+detailsDecoder : Decode.Decoder ConsentDetails
+detailsDecoder =
+    Decode.map2 ConsentDetails
+        (Decode.field "client_name" Decode.string)
+        (Decode.field "scopes" (Decode.list Decode.string))
+
+
+fetchDetails : String -> Cmd Msg
+fetchDetails challenge =
+    Http.get
+        { url = "/oauth2/consent/request?challenge=" ++ Url.percentEncode challenge
+        , expect = Http.expectJson GotDetails detailsDecoder
+        }
+
+
+update : Msg -> Model -> ( Model, Cmd Msg )
+update msg model =
+    case msg of
+        Noop ->
+            ( model, Cmd.none )
+
+        GotDetails (Ok details) ->
+            ( { model | details = Just details }, Cmd.none )
+
+        GotDetails (Err _) ->
+            ( { model | error = Just "This request has expired, please log in again" }, Cmd.none )
+
+
+initFromUrl : Url -> ( Model, Cmd Msg )
+initFromUrl url =
+    let
+        challenge =
+            parse (query <| Query.string "challenge") { url | path = "" } |> Maybe.andThen identity |> Maybe.withDefault ""
+    in
+    ( { challenge = challenge
+      , details = Nothing
+      , error = Nothing
+      }
+    , fetchDetails challenge
+    )
+
+
+main : Program () Model Msg
+main =
+    Browser.application
+        { init = \_ -> \url -> \_ -> initFromUrl url
+        , update = update
+        , view = view
+        , subscriptions = \_ -> Sub.none
+        , onUrlRequest = \_ -> Noop
+        , onUrlChange = \_ -> Noop
+        }
@@ -0,0 +1,242 @@
+module Device exposing (main)
+
+import Browser exposing (Document)
+import Css exposing (..)
+import Html.Styled exposing (Html, button, div, form, input, li, text, toUnstyled, ul)
+import Html.Styled.Attributes as Attributes exposing (autofocus, css, method, name, placeholder, type_, value)
+import Html.Styled.Events exposing (onInput, onSubmit)
+import Http
+import Json.Decode as Decode
+import Layout exposing (mainPage)
+import Loader exposing (loader)
+import Scope exposing (scopeDescription)
+import Url exposing (Url)
+import Url.Parser exposing (parse, query)
+import Url.Parser.Query as Query
+
+
+type alias DeviceDetails =
+    { userCode : String
+    , clientName : String
+    , scopes : List String
+    }
+
+
+type alias Model =
+    { userCode : String
+    , details : Maybe DeviceDetails
+    , error : Maybe String
+    , result : Maybe String
+    , loading : Bool
+    }
+
+
+type Msg
+    = Noop
+    | SetUserCode String
+    | Lookup
+    | GotDetails (Result Http.Error DeviceDetails)
+
+
+formStyle : Attributes.Attribute msg
+formStyle =
+    css
+        [ displayFlex
+        , flexDirection column
+        , border2 (px 1) solid
+        , borderRadius (px 10)
+        , padding (px 20)
+        ]
+
+
+errorMessage : String -> String
+errorMessage error =
+    case error of
+        "invalid_user_code" ->
+            "This code is invalid or has expired"
+
+        _ ->
+            error
+
+
+errorView : Model -> List (Html Msg)
+errorView model =
+    case model.error of
+        Just error ->
+            [ div [ css [ color (rgb 255 0 0), marginBottom (em 1) ] ] [ text <| errorMessage error ] ]
+
+        Nothing ->
+            []
+
+
+userCodeForm : Model -> Html Msg
+userCodeForm model =
+    form [ formStyle, onSubmit Lookup ] <|
+        [ div [ css [ marginBottom (em 1) ] ] [ text "Enter the code displayed on your device" ]
+        , div [ css [ marginBottom (em 1) ] ]
+            [ input
+                [ Attributes.disabled model.loading
+                , css [ width (pct 100) ]
+                , type_ "text"
+                , placeholder "XXXX-XXXX"
+                , value model.userCode
+                , autofocus True
+                , onInput SetUserCode
+                ]
+                []
+            ]
+        ]
+            ++ errorView model
+            ++ [ div [ css [ displayFlex, justifyContent center ] ]
+                    [ button
+                        [ Attributes.disabled model.loading
+                        , css [ minWidth (px 100), displayFlex, justifyContent center ]
+                        ]
+                      <|
+                        case model.loading of
+                            False ->
+                                [ text "Continue" ]
+
+                            True ->
+                                [ div [] [ loader 16 ] ]
+                    ]
+               ]
+
+
+approvalForm : Model -> DeviceDetails -> Html Msg
+approvalForm model details =
+    form [ formStyle, method "post" ] <|
+        [ div [] [ text <| details.clientName ++ " (code " ++ details.userCode ++ ") would like to:" ]
+        , ul [] <|
+            case details.scopes of
+                [] ->
+                    [ li [] [ text "Access your account" ] ]
+
+                scopes ->
+                    List.map (\scope -> li [] [ text <| scopeDescription scope ]) scopes
+        , input [ name "user_code", type_ "hidden", value details.userCode ] []
+        ]
+            ++ errorView model
+            ++ [ div [ css [ displayFlex, flexDirection row ] ]
+                    [ div [ css [ flexGrow (num 1) ] ] []
+                    , button
+                        [ name "decision"
+                        , value "deny"
+                        , css [ minWidth (px 100), marginRight (em 1) ]
+                        ]
+                        [ text "Deny" ]
+                    , button
+                        [ name "decision"
+                        , value "approve"
+                        , css [ minWidth (px 100) ]
+                        ]
+                        [ text "Allow" ]
+                    , div [ css [ flexGrow (num 1) ] ] []
+                    ]
+               ]
+
+
+resultMessage : String -> Html Msg
+resultMessage result =
+    div [ css [ fontSize (em 2), textAlign center ] ]
+        [ text <|
+            case result of
+                "approved" ->
+                    "Your device is now connected, you can return to it"
+
+                _ ->
+                    "The request has been denied"
+        ]
+
+
+view : Model -> Document Msg
+view model =
+    { title = "Connect a device"
+    , body =
+        [ toUnstyled <|
+            mainPage <|
+                case ( model.result, model.details ) of
+                    ( Just result, _ ) ->
+                        [ resultMessage result ]
+
+                    ( Nothing, Just details ) ->
+                        [ approvalForm model details ]
+
+                    ( Nothing, Nothing ) ->
+                        [ userCodeForm model ]
+        ]
+    }
+
+
+detailsDecoder : Decode.Decoder DeviceDetails
+detailsDecoder =
+    Decode.map3 DeviceDetails
+        (Decode.field "user_code" Decode.string)
+        (Decode.field "client_name" Decode.string)
+        (Decode.field "scopes" (Decode.list Decode.string))
+
+
+fetchDetails : String -> Cmd Msg
+fetchDetails userCode =
+    Http.get
+        { url = "/device/request?user_code=" ++ Url.percentEncode userCode
+        , expect = Http.expectJson GotDetails detailsDecoder
+        }
+
+
+update : Msg -> Model -> ( Model, Cmd Msg )
+update msg model =
+    case msg of
+        Noop ->
+            ( model, Cmd.none )
+
+        SetUserCode userCode ->
+            ( { model | userCode = userCode }, Cmd.none )
+
+        Lookup ->
+            ( { model | loading = True, error = Nothing }, fetchDetails model.userCode )
+
+        GotDetails (Ok details) ->
+            ( { model | loading = False, details = Just details }, Cmd.none )
+
+        GotDetails (Err _) ->
+            ( { model | loading = False, details = Nothing, error = Just "invalid_user_code" }, Cmd.none )
+
+
+initFromUrl : Url -> ( Model, Cmd Msg )
+initFromUrl url =
+    let
+        param key =
+            parse (query <| Query.string key) { url | path = "" } |> Maybe.andThen identity
+
+        userCode =
+            param "user_code"
+
+        result =
+            param "result"
+    in
+    ( { userCode = Maybe.withDefault "" userCode
+      , details = Nothing
+      , error = param "error"
+      , result = result
+      , loading = userCode /= Nothing && result == Nothing
+      }
+    , case ( userCode, result ) of
+        ( Just code, Nothing ) ->
+            fetchDetails code
+
+        _ ->
+            Cmd.none
+    )
+
+
+main : Program () Model Msg
+main =
+    Browser.application
+        { init = \_ -> \url -> \_ -> initFromUrl url
+        , update = update
+        , view = view
+        , subscriptions = \_ -> Sub.none
+        , onUrlRequest = \_ -> Noop
+        , onUrlChange = \_ -> Noop
+        }
@@ -0,0 +1,112 @@
+module Grants exposing (main)
+
+import Browser exposing (Document)
+import Css exposing (..)
+import Html.Styled exposing (Html, button, div, form, input, text, toUnstyled)
+import Html.Styled.Attributes exposing (action, css, method, name, type_, value)
+import Http
+import Json.Decode as Decode
+import Layout exposing (mainPage)
+import Loader exposing (loader)
+
+
+type alias Grant =
+    { clientId : String
+    , clientName : Maybe String
+    , scope : String
+    }
+
+
+type alias Model =
+    { grants : Maybe (List Grant)
+    , error : Maybe String
+    }
+
+
+type Msg
+    = GotGrants (Result Http.Error (List Grant))
+
+
+grantView : Grant -> Html Msg
+grantView grant =
+    form
+        [ method "post"
+        , action "/grants/revoke"
+        , css
+            [ displayFlex
+            , flexDirection row
+            , alignItems center
+            , border2 (px 1) solid
+            , borderRadius (px 10)
+            , padding (px 20)
+            , marginBottom (em 1)
+            ]
+        ]
+        [ div [ css [ displayFlex, flexDirection column, flexGrow (num 1) ] ]
+            [ div [] [ text <| Maybe.withDefault grant.clientId grant.clientName ]
+            , div [ css [ fontSize (em 0.8) ] ] [ text grant.scope ]
+            ]
+        , input [ name "client_id", type_ "hidden", value grant.clientId ] []
+        , button [ css [ minWidth (px 100) ] ] [ text "Revoke" ]
+        ]
+
+
+view : Model -> Document Msg
+view model =
+    { title = "Connected applications"
+    , body =
+        [ toUnstyled <|
+            mainPage <|
+                case ( model.grants, model.error ) of
+                    ( _, Just error ) ->
+                        [ div [ css [ color (rgb 255 0 0), textAlign center ] ] [ text error ] ]
+
+                    ( Just [], Nothing ) ->
+                        [ div [ css [ textAlign center ] ] [ text "No application has access to your account" ] ]
+
+                    ( Just grants, Nothing ) ->
+                        List.map grantView grants
+
+                    ( Nothing, Nothing ) ->
+                        [ div [ css [ displayFlex, justifyContent center ] ] [ loader 100 ] ]
+        ]
+    }
+
+
+grantDecoder : Decode.Decoder Grant
+grantDecoder =
+    Decode.map3 Grant
+        (Decode.field "client_id" Decode.string)
+        (Decode.field "client_name" (Decode.nullable Decode.string))
+        (Decode.field "scope" Decode.string)
+
+
+fetchGrants : Cmd Msg
+fetchGrants =
+    Http.get
+        { url = "/grants/list"
+        , expect = Http.expectJson GotGrants (Decode.list grantDecoder)
+        }
+
+
+update : Msg -> Model -> ( Model, Cmd Msg )
+update msg model =
+    case msg of
+        GotGrants (Ok grants) ->
+            ( { model | grants = Just grants }, Cmd.none )
+
+        GotGrants (Err (Http.BadStatus 401)) ->
+            ( { model | error = Just "Log in to an application first to manage its access" }, Cmd.none )
+
+        GotGrants (Err _) ->
+            ( { model | error = Just "Something went wrong, please try again later" }, Cmd.none )
+
+
+main : Program () Model Msg
+main =
+    Browser.document
+        { init = \_ -> ( { grants = Nothing, error = Nothing }, fetchGrants )
+        , update = update
+        , view = view
+        , subscriptions = \_ -> Sub.none
+        }
@@ -48,10 +48,20 @@ loginForm model =
                 ]
                 []
             ]
-        , div []
+        , div [] <|
             [ input [ name "client_id", type_ "hidden", value model.client_id ] []
-            , input [ name "redirect_uri", type_ "hidden", value model.redirect_uri ] []
             ]
+                ++ optionalHidden "redirect_uri" model.redirect_uri
+                ++ optionalHidden "scope" model.scope
+                ++ optionalHidden "nonce" model.nonce
+                ++ optionalHidden "code_challenge" model.code_challenge
+                ++ optionalHidden "code_challenge_method" model.code_challenge_method
+                ++ optionalHidden "state" model.state
+                ++ optionalHidden "prompt" model.prompt
+                ++ optionalHidden "max_age" model.max_age
+                ++ optionalHidden "resource" model.resource
+                ++ optionalHidden "request" model.request
+                ++ optionalHidden "request_uri" model.request_uri
         , div [] <|
             case model.error of
                 Just "not_activated" ->
@@ -91,6 +101,16 @@ loginForm model =
         ]
 
 
+optionalHidden : String -> Maybe String -> List (Html msg)
+optionalHidden fieldName fieldValue =
+    case fieldValue of
+        Just v ->
+            [ input [ name fieldName, type_ "hidden", value v ] [] ]
+
+        Nothing ->
+            []
+
+
 registerText : Html msg
 registerText =
     div [ css [ displayFlex, flexDirection column ] ]
@@ -118,7 +138,17 @@ view model =
 
 type alias Model =
     { client_id : String
-    , redirect_uri : String
+    , redirect_uri : Maybe String
+    , scope : Maybe String
+    , nonce : Maybe String
+    , code_challenge : Maybe String
+    , code_challenge_method : Maybe String
+    , state : Maybe String
+    , prompt : Maybe String
+    , max_age : Maybe String
+    , resource : Maybe String
+    , request : Maybe String
+    , request_uri : Maybe String
     , error : Maybe String
     , loading : Bool
     }
@@ -146,7 +176,17 @@ update msg model =
 modelFromUrl : Url.Url -> Model
 modelFromUrl url =
     { client_id = parse (query <| Query.string "client_id") url |> Maybe.andThen identity |> Maybe.withDefault ""
-    , redirect_uri = parse (query <| Query.string "redirect_uri") url |> Maybe.andThen identity |> Maybe.withDefault ""
+    , redirect_uri = parse (query <| Query.string "redirect_uri") url |> Maybe.andThen identity
+    , scope = parse (query <| Query.string "scope") url |> Maybe.andThen identity
+    , nonce = parse (query <| Query.string "nonce") url |> Maybe.andThen identity
+    , code_challenge = parse (query <| Query.string "code_challenge") url |> Maybe.andThen identity
+    , code_challenge_method = parse (query <| Query.string "code_challenge_method") url |> Maybe.andThen identity
+    , state = parse (query <| Query.string "state") url |> Maybe.andThen identity
+    , prompt = parse (query <| Query.string "prompt") url |> Maybe.andThen identity
+    , max_age = parse (query <| Query.string "max_age") url |> Maybe.andThen identity
+    , resource = parse (query <| Query.string "resource") url |> Maybe.andThen identity
+    , request = parse (query <| Query.string "request") url |> Maybe.andThen identity
+    , request_uri = parse (query <| Query.string "request_uri") url |> Maybe.andThen identity
     , error = parse (query <| Query.string "error") url |> Maybe.andThen identity
     , loading = False
     }
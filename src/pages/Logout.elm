@@ -0,0 +1,98 @@
+module Logout exposing (main)
+
+import Browser exposing (Document)
+import Css exposing (..)
+import Html.Styled exposing (Html, button, div, form, input, text, toUnstyled)
+import Html.Styled.Attributes exposing (css, method, name, type_, value)
+import Layout exposing (mainPage)
+import Url exposing (Url)
+import Url.Parser exposing (parse, query)
+import Url.Parser.Query as Query
+
+
+type alias Model =
+    { idTokenHint : Maybe String
+    , clientId : Maybe String
+    , postLogoutRedirectUri : Maybe String
+    , state : Maybe String
+    , loggedOut : Bool
+    }
+
+
+type Msg
+    = Noop
+
+
+optionalHidden : String -> Maybe String -> List (Html msg)
+optionalHidden fieldName fieldValue =
+    case fieldValue of
+        Just v ->
+            [ input [ name fieldName, type_ "hidden", value v ] [] ]
+
+        Nothing ->
+            []
+
+
+logoutForm : Model -> Html Msg
+logoutForm model =
+    form
+        [ method "post"
+        , css
+            [ displayFlex
+            , flexDirection column
+            , border2 (px 1) solid
+            , borderRadius (px 10)
+            , padding (px 20)
+            ]
+        ]
+        [ div [ css [ marginBottom (em 1) ] ] [ text "Do you want to log out?" ]
+        , div [] <|
+            optionalHidden "id_token_hint" model.idTokenHint
+                ++ optionalHidden "client_id" model.clientId
+                ++ optionalHidden "post_logout_redirect_uri" model.postLogoutRedirectUri
+                ++ optionalHidden "state" model.state
+        , div [ css [ displayFlex, justifyContent center ] ]
+            [ button [ css [ minWidth (px 100) ] ] [ text "Log out" ] ]
+        ]
+
+
+view : Model -> Document Msg
+view model =
+    { title = "Logout"
+    , body =
+        [ toUnstyled <|
+            mainPage
+                [ if model.loggedOut then
+                    div [ css [ fontSize (em 2), textAlign center ] ] [ text "You have been logged out" ]
+
+                  else
+                    logoutForm model
+                ]
+        ]
+    }
+
+
+modelFromUrl : Url -> Model
+modelFromUrl url =
+    let
+        param key =
+            parse (query <| Query.string key) { url | path = "" } |> Maybe.andThen identity
+    in
+    { idTokenHint = param "id_token_hint"
+    , clientId = param "client_id"
+    , postLogoutRedirectUri = param "post_logout_redirect_uri"
+    , state = param "state"
+    , loggedOut = param "logged_out" /= Nothing
+    }
+
+
+main : Program () Model Msg
+main =
+    Browser.application
+        { init = \_ -> \url -> \_ -> ( modelFromUrl url, Cmd.none )
+        , update = \_ -> \model -> ( model, Cmd.none )
+        , view = view
+        , subscriptions = \_ -> Sub.none
+        , onUrlRequest = \_ -> Noop
+        , onUrlChange = \_ -> Noop
+        }
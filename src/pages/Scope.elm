@@ -0,0 +1,17 @@
+module Scope exposing (scopeDescription)
+
+
+scopeDescription : String -> String
+scopeDescription scope =
+    case scope of
+        "openid" ->
+            "Sign you in with your account"
+
+        "profile" ->
+            "See your username"
+
+        "email" ->
+            "See your email address"
+
+        _ ->
+            scope
@@ -1,13 +1,36 @@
 use crate::helpers::{TokenHeader, Validatable, Validate};
-use crate::services::oauth2::{AccessToken, AccessTokenError, TokenParams};
+use crate::services::clients::Client;
+use crate::services::grants::ConsentRequest;
+use crate::services::oauth2::client_auth::{ClientAuthParams, ClientCredentials};
+use crate::services::oauth2::pkce::{CodeChallenge, PkceError};
+use crate::services::oauth2::{
+    AccessToken, AccessTokenError, AuthorizationRequest, Introspection, IntrospectionParams,
+    OauthErrorResponse, RevocationParams, ScopeError, TokenParams, UserInfo, UserInfoError,
+};
+use crate::services::pushed_authorizations::PushedAuthorizationService;
+use crate::services::sessions::{Session, SessionService};
+use crate::services::tokens::jwt::JwtVerifyError;
 use crate::services::users::{User, UserValidationError};
 use crate::Services;
-use axum::extract::State;
-use axum::http::StatusCode;
+use axum::extract::{Query, Request, State};
+use axum::http::{HeaderMap, StatusCode};
 use axum::response::{IntoResponse, Redirect, Response};
 use axum::{Form, Json};
+use axum_extra::extract::SignedCookieJar;
+use chrono::Utc;
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
+use tower::util::ServiceExt;
+use tower_http::services::ServeFile;
+use uuid::Uuid;
+
+pub mod admin;
+pub mod consent;
+pub mod device;
+pub mod grants;
+pub mod logout;
+pub mod registration;
+pub mod well_known;
 
 #[derive(Deserialize)]
 pub struct RegisterForm {
@@ -62,43 +85,584 @@ pub async fn register(
     (StatusCode::CREATED, String::new()).into_response()
 }
 
-#[derive(Deserialize)]
-pub struct LoginForm {
-    username: String,
-    password: String,
+/// Parameters of an authorization request, carried through the login page.
+#[derive(Serialize, Deserialize)]
+pub struct AuthorizationParams {
     client_id: String,
-    redirect_uri: String,
+    redirect_uri: Option<String>,
+    scope: Option<String>,
+    nonce: Option<String>,
+    code_challenge: Option<String>,
+    code_challenge_method: Option<String>,
+    state: Option<String>,
+    prompt: Option<String>,
+    max_age: Option<String>,
+    resource: Option<String>,
+    /// Request object (RFC 9101), a JWT signed by the client carrying the parameters above.
+    request: Option<String>,
+    /// Reference to a pushed authorization request (RFC 9126) or to a request object, standing
+    /// in for the parameters above.
+    request_uri: Option<String>,
+    /// Whether the parameters were pushed by the client.
+    #[serde(skip)]
+    pushed: bool,
+    /// Whether the parameters come from a request object.
+    #[serde(skip)]
+    signed: bool,
 }
 
-pub async fn login(
-    services: State<Arc<Services>>,
-    Form(req): Form<LoginForm>,
-) -> Result<Redirect, Response> {
+impl AuthorizationParams {
+    fn has_prompt(&self, prompt: &str) -> bool {
+        self.prompt
+            .as_deref()
+            .is_some_and(|prompts| prompts.split_ascii_whitespace().any(|p| p == prompt))
+    }
+
+    /// The login page for this request, showing `error`.
+    fn login_uri(&self, error: &str) -> String {
+        let mut query = url::form_urlencoded::Serializer::new(String::new());
+        query
+            .append_pair("error", error)
+            .append_pair("client_id", &self.client_id);
+        // parameters passed by reference stay out of the url
+        if let Some(request_uri) = &self.request_uri {
+            query.append_pair("request_uri", request_uri);
+            return format!("/oauth2/login?{}", query.finish());
+        }
+        if let Some(request) = &self.request {
+            query.append_pair("request", request);
+            return format!("/oauth2/login?{}", query.finish());
+        }
+        let optional = [
+            ("redirect_uri", &self.redirect_uri),
+            ("scope", &self.scope),
+            ("nonce", &self.nonce),
+            ("code_challenge", &self.code_challenge),
+            ("code_challenge_method", &self.code_challenge_method),
+            ("state", &self.state),
+            ("prompt", &self.prompt),
+            ("max_age", &self.max_age),
+            ("resource", &self.resource),
+        ];
+        for (name, value) in optional {
+            if let Some(value) = value {
+                query.append_pair(name, value);
+            }
+        }
+
+        format!("/oauth2/login?{}", query.finish())
+    }
+
+    /// Parameters of a verified request object. Claims other than strings and numbers, like
+    /// nested requests, are ignored.
+    fn from_request_object(
+        client_id: String,
+        claims: &serde_json::Map<String, serde_json::Value>,
+    ) -> Self {
+        let claim = |name: &str| match claims.get(name) {
+            Some(serde_json::Value::String(value)) => Some(value.clone()),
+            Some(serde_json::Value::Number(value)) => Some(value.to_string()),
+            _ => None,
+        };
+
+        Self {
+            client_id,
+            redirect_uri: claim("redirect_uri"),
+            scope: claim("scope"),
+            nonce: claim("nonce"),
+            code_challenge: claim("code_challenge"),
+            code_challenge_method: claim("code_challenge_method"),
+            state: claim("state"),
+            prompt: claim("prompt"),
+            max_age: claim("max_age"),
+            resource: claim("resource"),
+            request: None,
+            request_uri: None,
+            pushed: false,
+            signed: true,
+        }
+    }
+
+    /// Whether the parameters are kept server side, under a `request_uri` of ours.
+    fn is_stored(&self) -> bool {
+        self.request_uri
+            .as_deref()
+            .is_some_and(PushedAuthorizationService::is_request_uri)
+    }
+}
+
+/// Authorization parameters kept server side under a `request_uri`, along with how they were
+/// received.
+#[derive(Serialize, Deserialize)]
+struct StoredRequest {
+    params: AuthorizationParams,
+    pushed: bool,
+    signed: bool,
+}
+
+impl StoredRequest {
+    fn new(mut params: AuthorizationParams) -> Self {
+        params.request = None;
+        params.request_uri = None;
+
+        Self {
+            pushed: params.pushed,
+            signed: params.signed,
+            params,
+        }
+    }
+}
+
+/// Why an authorization request was rejected.
+enum AuthorizationRequestError {
+    /// The client or its redirect uri is unknown, so the error can only be shown to the user.
+    Invalid(&'static str),
+    /// The request object cannot be verified, so none of its parameters can be trusted.
+    InvalidRequestObject(&'static str),
+    /// An error sent back to the client's registered redirect uri.
+    Client {
+        redirect_uri: String,
+        state: Option<String>,
+        error: &'static str,
+        description: String,
+    },
+    InternalError(Response),
+}
+
+impl AuthorizationRequestError {
+    fn client(
+        redirect_uri: &str,
+        state: Option<&str>,
+        error: &'static str,
+        description: impl Into<String>,
+    ) -> Self {
+        Self::Client {
+            redirect_uri: redirect_uri.to_string(),
+            state: state.map(ToString::to_string),
+            error,
+            description: description.into(),
+        }
+    }
+
+    fn internal(error: impl IntoResponse) -> Self {
+        Self::InternalError(error.into_response())
+    }
+
+    /// Pushed requests are rejected in the response to the client (RFC 9126 section 2.3).
+    fn into_pushed_response(self) -> Response {
+        match self {
+            Self::Invalid(description) => (
+                StatusCode::BAD_REQUEST,
+                Json(OauthErrorResponse {
+                    error: "invalid_request",
+                    error_description: Some(description),
+                }),
+            )
+                .into_response(),
+            Self::InvalidRequestObject(description) => (
+                StatusCode::BAD_REQUEST,
+                Json(OauthErrorResponse {
+                    error: "invalid_request_object",
+                    error_description: Some(description),
+                }),
+            )
+                .into_response(),
+            Self::Client {
+                error, description, ..
+            } => (
+                StatusCode::BAD_REQUEST,
+                Json(serde_json::json!({
+                    "error": error,
+                    "error_description": description,
+                })),
+            )
+                .into_response(),
+            Self::InternalError(response) => response,
+        }
+    }
+}
+
+impl IntoResponse for AuthorizationRequestError {
+    fn into_response(self) -> Response {
+        match self {
+            Self::Invalid(description) | Self::InvalidRequestObject(description) => {
+                (StatusCode::BAD_REQUEST, description).into_response()
+            }
+            Self::Client {
+                redirect_uri,
+                state,
+                error,
+                description,
+            } => authorization_error(&redirect_uri, state.as_deref(), error, &description),
+            Self::InternalError(response) => response,
+        }
+    }
+}
+
+/// Replaces the parameters of a request passed by reference, as a `request` object or a
+/// `request_uri`, by the ones it refers to. Only the client id is taken from the request itself.
+async fn resolve_request(
+    services: &Services,
+    params: AuthorizationParams,
+) -> Result<AuthorizationParams, AuthorizationRequestError> {
+    if params.is_stored() {
+        return stored_request(services, params).await;
+    }
+    match (&params.request, &params.request_uri) {
+        (None, None) => return Ok(params),
+        (Some(_), Some(_)) => {
+            return Err(AuthorizationRequestError::Invalid(
+                "request and request_uri cannot be used together",
+            ))
+        }
+        _ => {}
+    }
+
+    let client = services
+        .client_service
+        .get_by_client_id(&params.client_id)
+        .await
+        .map_err(AuthorizationRequestError::internal)?
+        .ok_or(AuthorizationRequestError::Invalid("client_id is invalid"))?;
+    let request = match (&params.request, &params.request_uri) {
+        (Some(request), _) => request.clone(),
+        (None, Some(request_uri)) => services
+            .client_service
+            .fetch_request_object(&client, request_uri)
+            .await
+            .ok_or(AuthorizationRequestError::Invalid(
+                "request_uri cannot be used",
+            ))?,
+        (None, None) => unreachable!("checked above"),
+    };
+
+    let claims = verify_request_object(services, &client, &request).await?;
+    let mut resolved = AuthorizationParams::from_request_object(params.client_id, &claims);
+    resolved.request = params.request;
+    resolved.request_uri = params.request_uri;
+    Ok(resolved)
+}
+
+/// The parameters stored under one of our `request_uri`s, pushed or from a request object.
+async fn stored_request(
+    services: &Services,
+    params: AuthorizationParams,
+) -> Result<AuthorizationParams, AuthorizationRequestError> {
+    let Some(request_uri) = &params.request_uri else {
+        return Ok(params);
+    };
+
+    let stored: StoredRequest = services
+        .pushed_authorization_service
+        .get(request_uri)
+        .await
+        .map_err(AuthorizationRequestError::internal)?
+        .ok_or(AuthorizationRequestError::Invalid(
+            "request_uri is invalid or expired",
+        ))?;
+    if stored.params.client_id != params.client_id {
+        tracing::info!(
+            client_id = params.client_id,
+            stored.params.client_id,
+            "request_uri was stored for another client"
+        );
+        return Err(AuthorizationRequestError::Invalid(
+            "client_id does not match request_uri",
+        ));
+    }
+
+    let mut resolved = stored.params;
+    resolved.request_uri = params.request_uri;
+    resolved.pushed = stored.pushed;
+    resolved.signed = stored.signed;
+    Ok(resolved)
+}
+
+/// Verifies a request object against the client's keys. Its claims are only trusted once the
+/// signature checks out, so errors cannot be sent to its redirect uri.
+async fn verify_request_object(
+    services: &Services,
+    client: &Client,
+    request: &str,
+) -> Result<serde_json::Map<String, serde_json::Value>, AuthorizationRequestError> {
+    let Some(jwks) = services.client_service.jwks(client).await else {
+        return Err(AuthorizationRequestError::InvalidRequestObject(
+            "client has no keys to verify request objects with",
+        ));
+    };
+
+    let claims = services
+        .token_service
+        .verify_request_object(request, &client.client_id, &jwks)
+        .map_err(|e| {
+            tracing::info!(client.client_id, error = %e, "invalid request object");
+            AuthorizationRequestError::InvalidRequestObject("request object is invalid")
+        })?;
+    if claims
+        .get("client_id")
+        .is_some_and(|client_id| client_id.as_str() != Some(&client.client_id))
+    {
+        return Err(AuthorizationRequestError::InvalidRequestObject(
+            "client_id does not match request object",
+        ));
+    }
+
+    Ok(claims)
+}
+
+/// A stored request can only lead to one authorization code or consent request, so it is
+/// claimed right before it is used.
+async fn claim_stored_request(
+    services: &Services,
+    params: &AuthorizationParams,
+) -> Result<(), Response> {
+    if let Some(request_uri) = params.request_uri.as_ref().filter(|_| params.is_stored()) {
+        if !services
+            .pushed_authorization_service
+            .claim(request_uri)
+            .await
+            .map_err(IntoResponse::into_response)?
+        {
+            return Err(
+                AuthorizationRequestError::Invalid("request_uri is invalid or expired")
+                    .into_response(),
+            );
+        }
+    }
+
+    Ok(())
+}
+
+/// Validates an authorization request. Once the redirect uri is known to be registered, errors
+/// are sent back to the client.
+async fn authorization_request(
+    services: &Services,
+    params: &AuthorizationParams,
+) -> Result<(Client, AuthorizationRequest), AuthorizationRequestError> {
     // check for client_id and redirect_uri
     let client = services
         .client_service
-        .get_by_client_id(&req.client_id)
+        .get_by_client_id(&params.client_id)
         .await
-        .map_err(IntoResponse::into_response)?
-        .ok_or((StatusCode::BAD_REQUEST, "client_id is invalid").into_response())?;
+        .map_err(AuthorizationRequestError::internal)?
+        .ok_or(AuthorizationRequestError::Invalid("client_id is invalid"))?;
 
-    if req.redirect_uri != client.redirect_uri {
+    let Some(redirect_uri) = client
+        .resolve_redirect_uri(params.redirect_uri.as_deref())
+        .map(ToString::to_string)
+    else {
         tracing::info!(
-            redirect_uri.expected = client.redirect_uri,
-            redirect_uri.actual = req.redirect_uri,
-            "redirect_uri does not match client's redirect_uri"
+            client_id = params.client_id,
+            redirect_uri = params.redirect_uri,
+            "redirect_uri is not registered for the client"
         );
-        return Err((StatusCode::BAD_REQUEST, "redirect_uri mismatch").into_response());
+        return Err(AuthorizationRequestError::Invalid("redirect_uri mismatch"));
+    };
+
+    if client.require_pushed_authorization_requests && !params.pushed {
+        tracing::info!(
+            client_id = params.client_id,
+            "client requires pushed authorization requests"
+        );
+        return Err(AuthorizationRequestError::client(
+            &redirect_uri,
+            params.state.as_deref(),
+            "invalid_request",
+            "authorization requests must be pushed",
+        ));
     }
+    if client.require_signed_request_object && !params.signed {
+        tracing::info!(
+            client_id = params.client_id,
+            "client requires signed request objects"
+        );
+        return Err(AuthorizationRequestError::client(
+            &redirect_uri,
+            params.state.as_deref(),
+            "invalid_request",
+            "authorization requests must be signed request objects",
+        ));
+    }
+
+    if params
+        .max_age
+        .as_ref()
+        .is_some_and(|max_age| max_age.parse::<u32>().is_err())
+    {
+        return Err(AuthorizationRequestError::client(
+            &redirect_uri,
+            params.state.as_deref(),
+            "invalid_request",
+            "max_age must be a number of seconds",
+        ));
+    }
+
+    let code_challenge = match &params.code_challenge {
+        Some(challenge) => Some(CodeChallenge::new(
+            challenge.clone(),
+            params.code_challenge_method.as_deref(),
+        )),
+        None if client.require_pkce => Some(Err(PkceError::ChallengeRequired)),
+        None => None,
+    }
+    .transpose()
+    .map_err(|e| {
+        tracing::info!(client_id = params.client_id, error = %e, "invalid code challenge");
+        AuthorizationRequestError::client(
+            &redirect_uri,
+            params.state.as_deref(),
+            "invalid_request",
+            e.to_string(),
+        )
+    })?;
+
+    let resource = match services
+        .oauth2_service
+        .resolve_resource(params.resource.as_deref(), None)
+        .await
+    {
+        Ok(resource) => resource,
+        Err(AccessTokenError::InvalidTarget(description)) => {
+            tracing::info!(
+                client_id = params.client_id,
+                resource = params.resource,
+                "invalid resource"
+            );
+            return Err(AuthorizationRequestError::client(
+                &redirect_uri,
+                params.state.as_deref(),
+                "invalid_target",
+                description,
+            ));
+        }
+        Err(e) => return Err(AuthorizationRequestError::internal(e)),
+    };
 
-    let login_uri = |error| {
-        Redirect::to(&format!(
-            "/oauth2/login?error={}&client_id={}&redirect_uri={}",
-            error, req.client_id, req.redirect_uri
-        ))
-        .into_response()
+    let scope = match services
+        .oauth2_service
+        .grant_scope(&client, params.scope.as_deref())
+        .await
+    {
+        Ok(scope) => scope,
+        Err(ScopeError::NotAllowed(scope)) => {
+            tracing::info!(client_id = params.client_id, scope, "scope not allowed");
+            return Err(AuthorizationRequestError::client(
+                &redirect_uri,
+                params.state.as_deref(),
+                "invalid_scope",
+                format!("scope not allowed: {}", scope),
+            ));
+        }
+        Err(ScopeError::InternalError(e)) => return Err(AuthorizationRequestError::internal(e)),
     };
 
+    let request = AuthorizationRequest {
+        client_id: params.client_id.clone(),
+        redirect_uri,
+        redirect_uri_sent: params.redirect_uri.is_some(),
+        scope,
+        nonce: params.nonce.clone(),
+        code_challenge,
+        state: params.state.clone(),
+        resource,
+    };
+    Ok((client, request))
+}
+
+/// Authorization endpoint. Users with a valid session are sent back to the client right away,
+/// others get the login page.
+pub async fn authorization(
+    services: State<Arc<Services>>,
+    Query(params): Query<AuthorizationParams>,
+    request: Request,
+) -> Result<Response, Response> {
+    let params = resolve_request(&services, params)
+        .await
+        .map_err(IntoResponse::into_response)?;
+    let (client, authorization) = authorization_request(&services, &params)
+        .await
+        .map_err(IntoResponse::into_response)?;
+    let jar = services.session_service.cookie_jar(request.headers());
+
+    let session = match jar.get(SessionService::COOKIE_NAME) {
+        Some(cookie) => services
+            .session_service
+            .get(cookie.value())
+            .await
+            .map_err(IntoResponse::into_response)?,
+        None => None,
+    };
+    let max_age = params
+        .max_age
+        .as_ref()
+        .and_then(|max_age| max_age.parse().ok());
+    let session = session.filter(|session| {
+        !params.has_prompt("login")
+            && !max_age.is_some_and(|max_age| {
+                session.auth_time + chrono::Duration::seconds(max_age) < Utc::now()
+            })
+    });
+
+    match session {
+        Some(session) => {
+            claim_stored_request(&services, &params).await?;
+            let redirect = authorize(
+                &services,
+                &client,
+                session,
+                authorization,
+                !params.has_prompt("none"),
+            )
+            .await?;
+            Ok(redirect.into_response())
+        }
+        None if params.has_prompt("none") => Err(authorization_error(
+            &authorization.redirect_uri,
+            authorization.state.as_deref(),
+            "login_required",
+            "the user must log in",
+        )),
+        // a request object is verified once, then kept server side while the user logs in
+        None if params.signed && !params.is_stored() => {
+            let request_uri = services
+                .pushed_authorization_service
+                .push(&StoredRequest::new(params))
+                .await
+                .map_err(IntoResponse::into_response)?;
+            let query = url::form_urlencoded::Serializer::new(String::new())
+                .append_pair("client_id", &client.client_id)
+                .append_pair("request_uri", &request_uri)
+                .finish();
+            Ok(Redirect::to(&format!("/oauth2/login?{}", query)).into_response())
+        }
+        None => Ok(ServeFile::new("static/login.html")
+            .oneshot(request)
+            .await
+            .into_response()),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct LoginForm {
+    username: String,
+    password: String,
+    #[serde(flatten)]
+    params: AuthorizationParams,
+}
+
+pub async fn login(
+    services: State<Arc<Services>>,
+    headers: HeaderMap,
+    Form(req): Form<LoginForm>,
+) -> Result<(SignedCookieJar, Redirect), Response> {
+    let params = resolve_request(&services, req.params)
+        .await
+        .map_err(IntoResponse::into_response)?;
+    let (client, authorization) = authorization_request(&services, &params)
+        .await
+        .map_err(IntoResponse::into_response)?;
+    let jar = services.session_service.cookie_jar(&headers);
+
     // check for password
     let user = match services
         .user_service
@@ -108,47 +672,319 @@ pub async fn login(
         Ok(user) => Ok(user),
         Err(UserValidationError::UserNotFound) => {
             tracing::info!(username = &req.username, "user not found");
-            Err(login_uri("invalid_credentials"))
+            Err(Redirect::to(&params.login_uri("invalid_credentials")).into_response())
         }
         Err(UserValidationError::InvalidPassword) => {
             tracing::info!(username = &req.username, "invalid password");
-            Err(login_uri("invalid_credentials"))
+            Err(Redirect::to(&params.login_uri("invalid_credentials")).into_response())
         }
         Err(UserValidationError::NotActivated) => {
             tracing::info!(username = &req.username, "user not activated");
-            Err(login_uri("not_activated"))
+            Err(Redirect::to(&params.login_uri("not_activated")).into_response())
         }
         Err(UserValidationError::InternalError(_)) => {
             Err((StatusCode::INTERNAL_SERVER_ERROR, "").into_response())
         }
     }?;
+    claim_stored_request(&services, &params).await?;
+    let (jar, session) = start_session(&services, jar, user.id).await?;
+
+    let redirect = authorize(&services, &client, session, authorization, true).await?;
+    Ok((jar, redirect))
+}
+
+/// Logs the user in with a fresh session, so that a planted session id is never authenticated.
+/// The sid is kept when the same user logs in again, otherwise the old session has ended.
+async fn start_session(
+    services: &Services,
+    jar: SignedCookieJar,
+    user_id: Uuid,
+) -> Result<(SignedCookieJar, Session), Response> {
+    let mut sid = None;
+    if let Some(cookie) = jar.get(SessionService::COOKIE_NAME) {
+        let old_session = services
+            .session_service
+            .get(cookie.value())
+            .await
+            .map_err(IntoResponse::into_response)?;
+        match old_session {
+            Some(old_session) if old_session.user_id == user_id => {
+                services
+                    .session_service
+                    .delete(cookie.value())
+                    .await
+                    .map_err(IntoResponse::into_response)?;
+                sid = Some(old_session.sid);
+            }
+            Some(old_session) => {
+                let client_ids = services
+                    .session_service
+                    .end(cookie.value(), &old_session)
+                    .await
+                    .map_err(IntoResponse::into_response)?;
+                services
+                    .backchannel_logout_service
+                    .notify(&old_session, client_ids);
+            }
+            None => {}
+        }
+    }
+    let (session_id, session) = services
+        .session_service
+        .create(user_id, sid)
+        .await
+        .map_err(IntoResponse::into_response)?;
+
+    Ok((
+        jar.add(services.session_service.cookie(session_id)),
+        session,
+    ))
+}
+
+/// Parameters of a pushed authorization request (RFC 9126).
+#[derive(Deserialize)]
+pub struct PushedAuthorizationForm {
+    #[serde(flatten)]
+    params: AuthorizationParams,
+    #[serde(flatten)]
+    client_auth: ClientAuthParams,
+}
+
+#[derive(Serialize)]
+pub struct PushedAuthorizationResponse {
+    request_uri: String,
+    expires_in: u64,
+}
+
+/// Pushed authorization request endpoint. Clients authenticate like at the token endpoint, and
+/// the request is validated right away, so errors go to the client rather than to the user.
+pub async fn pushed_authorization_request(
+    services: State<Arc<Services>>,
+    authorization: Option<TokenHeader>,
+    Form(req): Form<PushedAuthorizationForm>,
+) -> Result<(StatusCode, Json<PushedAuthorizationResponse>), Response> {
+    // the client id is one of the authorization parameters, which authentication must agree with
+    let client_auth = req.client_auth.set_client_id(req.params.client_id.clone());
+    let credentials = ClientCredentials::from_request(authorization.as_ref(), &client_auth)
+        .map_err(IntoResponse::into_response)?;
+    services
+        .oauth2_service
+        .authenticate_client(&credentials)
+        .await
+        .map_err(IntoResponse::into_response)?;
+
+    if req.params.request_uri.is_some() {
+        return Err(
+            AccessTokenError::InvalidRequest("request_uri must not be pushed").into_response(),
+        );
+    }
+    let mut params = resolve_request(&services, req.params)
+        .await
+        .map_err(AuthorizationRequestError::into_pushed_response)?;
+    params.pushed = true;
+    authorization_request(&services, &params)
+        .await
+        .map_err(AuthorizationRequestError::into_pushed_response)?;
+
+    let client_id = params.client_id.clone();
+    let request_uri = services
+        .pushed_authorization_service
+        .push(&StoredRequest::new(params))
+        .await
+        .map_err(IntoResponse::into_response)?;
+    tracing::info!(client_id, "authorization request pushed");
+
+    Ok((
+        StatusCode::CREATED,
+        Json(PushedAuthorizationResponse {
+            request_uri,
+            expires_in: PushedAuthorizationService::EXPIRES_IN,
+        }),
+    ))
+}
+
+/// Issues the authorization code right away when the user already approved the request (or the
+/// client skips consent), otherwise hands the request over to the consent page. Without
+/// `interactive`, a request that needs consent fails instead.
+async fn authorize(
+    services: &Services,
+    client: &Client,
+    session: Session,
+    request: AuthorizationRequest,
+    interactive: bool,
+) -> Result<Redirect, Response> {
+    let consented = client.skip_consent
+        || services
+            .grant_service
+            .is_granted(session.user_id, client, request.scope.as_deref())
+            .await
+            .map_err(IntoResponse::into_response)?;
+
+    if !consented {
+        if !interactive {
+            return Err(authorization_error(
+                &request.redirect_uri,
+                request.state.as_deref(),
+                "consent_required",
+                "the user must approve the request",
+            ));
+        }
+
+        let challenge = services
+            .grant_service
+            .save_consent_request(&ConsentRequest { session, request })
+            .await
+            .map_err(IntoResponse::into_response)?;
+
+        let query = url::form_urlencoded::Serializer::new(String::new())
+            .append_pair("challenge", &challenge)
+            .finish();
+        return Ok(Redirect::to(&format!("/oauth2/consent?{}", query)));
+    }
+
+    redirect_with_code(services, &session, request).await
+}
+
+/// Redirects back to the client with an authorization code, remembering that the client was
+/// used with the session for back-channel logout.
+async fn redirect_with_code(
+    services: &Services,
+    session: &Session,
+    request: AuthorizationRequest,
+) -> Result<Redirect, Response> {
+    let redirect_uri = request.redirect_uri.clone();
+    let state = request.state.clone();
+
+    services
+        .session_service
+        .add_client(&session.sid, &request.client_id)
+        .await
+        .map_err(IntoResponse::into_response)?;
 
     // generate authorization code
     let auth_code = services
         .oauth2_service
-        .create_authorization_code(req.client_id, user.id)
+        .create_authorization_code(session, request)
         .map_err(IntoResponse::into_response)?;
 
-    let redirect_url = url::Url::parse_with_params(&req.redirect_uri, &[("code", &auth_code)])
-        .map_err(|e| {
-            (
-                StatusCode::BAD_REQUEST,
-                format!("invalid redirect url: {}", e),
-            )
-                .into_response()
-        })?;
+    let mut params = vec![("code", auth_code.as_str())];
+    if let Some(state) = &state {
+        params.push(("state", state.as_str()));
+    }
+
+    let redirect_url = url::Url::parse_with_params(&redirect_uri, &params).map_err(|e| {
+        (
+            StatusCode::BAD_REQUEST,
+            format!("invalid redirect url: {}", e),
+        )
+            .into_response()
+    })?;
     Ok(Redirect::to(redirect_url.as_ref()))
 }
 
+/// Sends an authorization error back to the client's (already validated) redirect uri.
+fn authorization_error(
+    redirect_uri: &str,
+    state: Option<&str>,
+    error: &str,
+    description: &str,
+) -> Response {
+    let mut params = vec![("error", error), ("error_description", description)];
+    if let Some(state) = state {
+        params.push(("state", state));
+    }
+
+    match url::Url::parse_with_params(redirect_uri, &params) {
+        Ok(url) => Redirect::to(url.as_ref()).into_response(),
+        Err(e) => (
+            StatusCode::BAD_REQUEST,
+            format!("invalid redirect url: {}", e),
+        )
+            .into_response(),
+    }
+}
+
 pub async fn token(
     services: State<Arc<Services>>,
-    token_form: Form<TokenParams>,
+    authorization: Option<TokenHeader>,
+    Form(params): Form<TokenParams>,
 ) -> Result<Json<AccessToken>, AccessTokenError> {
+    let credentials = ClientCredentials::from_request(authorization.as_ref(), &params.client_auth)?;
+
+    Ok(Json(
+        services
+            .oauth2_service
+            .access_token(&credentials, &params)
+            .await?,
+    ))
+}
+
+pub async fn introspect(
+    services: State<Arc<Services>>,
+    authorization: Option<TokenHeader>,
+    Form(params): Form<IntrospectionParams>,
+) -> Result<Json<Introspection>, AccessTokenError> {
+    let credentials = ClientCredentials::from_request(authorization.as_ref(), &params.client_auth)?;
+
     Ok(Json(
-        services.oauth2_service.access_token(&token_form).await?,
+        services
+            .oauth2_service
+            .introspect(&credentials, &params)
+            .await?,
     ))
 }
 
+pub async fn revoke(
+    services: State<Arc<Services>>,
+    authorization: Option<TokenHeader>,
+    Form(params): Form<RevocationParams>,
+) -> Result<(), AccessTokenError> {
+    let credentials = ClientCredentials::from_request(authorization.as_ref(), &params.client_auth)?;
+
+    services.oauth2_service.revoke(&credentials, &params).await
+}
+
+/// Access token sent in the `Authorization` header or, for form posts, in the body (RFC 6750
+/// section 2). Clients must use only one of them.
+fn bearer_token<'a>(
+    header: Option<&'a TokenHeader>,
+    body: Option<&'a str>,
+) -> Result<&'a str, UserInfoError> {
+    match (header, body) {
+        (Some(header), None) => header
+            .to_bearer_token()
+            .map_err(|_| UserInfoError::InvalidRequest),
+        (None, Some(body)) => Ok(body),
+        (Some(_), Some(_)) => Err(UserInfoError::InvalidRequest),
+        (None, None) => Err(UserInfoError::MissingToken),
+    }
+}
+
+pub async fn userinfo(
+    services: State<Arc<Services>>,
+    token: Option<TokenHeader>,
+) -> Result<Json<UserInfo>, UserInfoError> {
+    let access_token = bearer_token(token.as_ref(), None)?;
+
+    Ok(Json(services.oauth2_service.userinfo(access_token).await?))
+}
+
+#[derive(Deserialize)]
+pub struct UserInfoForm {
+    access_token: Option<String>,
+}
+
+pub async fn userinfo_form(
+    services: State<Arc<Services>>,
+    token: Option<TokenHeader>,
+    form: Option<Form<UserInfoForm>>,
+) -> Result<Json<UserInfo>, UserInfoError> {
+    let body = form.as_ref().and_then(|form| form.access_token.as_deref());
+    let access_token = bearer_token(token.as_ref(), body)?;
+
+    Ok(Json(services.oauth2_service.userinfo(access_token).await?))
+}
+
 #[derive(Serialize)]
 pub struct Profile {
     username: String,
@@ -157,19 +993,21 @@ pub struct Profile {
 
 pub async fn profile(
     services: State<Arc<Services>>,
-    token: TokenHeader,
-) -> Result<Json<Profile>, Response> {
+    token: Option<TokenHeader>,
+) -> Result<Json<Profile>, UserInfoError> {
     let claims = services
         .token_service
-        .verify_access_token(token.to_bearer_token()?)
-        .map_err(IntoResponse::into_response)?;
+        .verify_own_access_token(bearer_token(token.as_ref(), None)?)
+        .await?;
 
+    let user_id = claims
+        .user_id()
+        .ok_or(UserInfoError::InvalidToken("token subject is not a user"))?;
     let user = services
         .user_service
-        .get_by_id(claims.sub)
-        .await
-        .map_err(IntoResponse::into_response)?
-        .ok_or((StatusCode::UNAUTHORIZED, "user not found").into_response())?;
+        .get_by_id(user_id)
+        .await?
+        .ok_or(UserInfoError::InvalidToken("token subject is not a user"))?;
 
     Ok(Json(Profile {
         username: user.username,
@@ -218,6 +1056,7 @@ pub async fn send_activation_email(
     Ok(())
 }
 
+#[allow(clippy::result_large_err)]
 fn generate_and_send_activation_email(
     services: State<Arc<Services>>,
     user: User,
@@ -249,9 +1088,13 @@ pub async fn activate(
         .verify_activation_code(&query.code)
         .map_err(IntoResponse::into_response)?;
 
+    let user_id = claims
+        .user_id()
+        .ok_or(JwtVerifyError::InvalidToken)
+        .map_err(IntoResponse::into_response)?;
     services
         .user_service
-        .activate(claims.sub)
+        .activate(user_id)
         .await
         .map_err(IntoResponse::into_response)?;
 
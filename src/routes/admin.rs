@@ -0,0 +1,41 @@
+use crate::helpers::TokenHeader;
+use crate::Services;
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use std::sync::Arc;
+use uuid::Uuid;
+
+/// Ends every session of a user and notifies the clients they were used with over the back
+/// channel.
+pub async fn end_sessions(
+    services: State<Arc<Services>>,
+    token: Option<TokenHeader>,
+    Path(user_id): Path<Uuid>,
+) -> Result<StatusCode, Response> {
+    if !token
+        .as_ref()
+        .and_then(|token| token.to_bearer_token().ok())
+        .is_some_and(|token| services.session_service.is_admin_token(token))
+    {
+        return Err((StatusCode::UNAUTHORIZED, "invalid admin token").into_response());
+    }
+
+    let ended = services
+        .session_service
+        .end_all(user_id)
+        .await
+        .map_err(IntoResponse::into_response)?;
+    for (session, client_ids) in &ended {
+        services
+            .backchannel_logout_service
+            .notify(session, client_ids.clone());
+    }
+    tracing::info!(
+        user.id = user_id.to_string(),
+        sessions = ended.len(),
+        "ended user sessions"
+    );
+
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -0,0 +1,101 @@
+use crate::routes::{authorization_error, redirect_with_code};
+use crate::services::oauth2::scope;
+use crate::Services;
+use axum::extract::{Query, State};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Redirect, Response};
+use axum::{Form, Json};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+#[derive(Deserialize)]
+pub struct ConsentQuery {
+    challenge: String,
+}
+
+/// What the consent page shows the user.
+#[derive(Serialize)]
+pub struct ConsentDetails {
+    client_name: String,
+    scopes: Vec<String>,
+}
+
+pub async fn consent_request(
+    services: State<Arc<Services>>,
+    Query(query): Query<ConsentQuery>,
+) -> Result<Json<ConsentDetails>, Response> {
+    let consent = services
+        .grant_service
+        .get_consent_request(&query.challenge)
+        .await
+        .map_err(IntoResponse::into_response)?
+        .ok_or((StatusCode::NOT_FOUND, "consent request not found").into_response())?;
+
+    let client = services
+        .client_service
+        .get_by_client_id(&consent.request.client_id)
+        .await
+        .map_err(IntoResponse::into_response)?
+        .ok_or((StatusCode::NOT_FOUND, "client not found").into_response())?;
+
+    Ok(Json(ConsentDetails {
+        client_name: client.display_name().to_string(),
+        scopes: consent
+            .request
+            .scope
+            .as_deref()
+            .map(scope::parse)
+            .unwrap_or_default()
+            .into_iter()
+            .map(ToString::to_string)
+            .collect(),
+    }))
+}
+
+#[derive(Deserialize)]
+pub struct ConsentForm {
+    challenge: String,
+    decision: String,
+}
+
+pub async fn consent(
+    services: State<Arc<Services>>,
+    Form(form): Form<ConsentForm>,
+) -> Result<Redirect, Response> {
+    let consent = services
+        .grant_service
+        .take_consent_request(&form.challenge)
+        .await
+        .map_err(IntoResponse::into_response)?
+        .ok_or((StatusCode::BAD_REQUEST, "consent request expired").into_response())?;
+    let request = consent.request;
+
+    if form.decision != "approve" {
+        tracing::info!(
+            client_id = request.client_id,
+            user.id = consent.session.user_id.to_string(),
+            "user denied consent"
+        );
+        return Err(authorization_error(
+            &request.redirect_uri,
+            request.state.as_deref(),
+            "access_denied",
+            "the user denied the request",
+        ));
+    }
+
+    let client = services
+        .client_service
+        .get_by_client_id(&request.client_id)
+        .await
+        .map_err(IntoResponse::into_response)?
+        .ok_or((StatusCode::BAD_REQUEST, "client_id is invalid").into_response())?;
+
+    services
+        .grant_service
+        .grant(consent.session.user_id, &client, request.scope.as_deref())
+        .await
+        .map_err(IntoResponse::into_response)?;
+
+    redirect_with_code(&services, &consent.session, request).await
+}
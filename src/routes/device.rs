@@ -0,0 +1,216 @@
+use crate::helpers::TokenHeader;
+use crate::routes::start_session;
+use crate::services::devices::{DeviceService, DeviceStatus};
+use crate::services::oauth2::client_auth::ClientCredentials;
+use crate::services::oauth2::{
+    scope, AccessTokenError, DeviceAuthorizationParams, DeviceAuthorizationResponse,
+};
+use crate::services::sessions::SessionService;
+use crate::services::users::UserValidationError;
+use crate::Services;
+use axum::extract::{Query, State};
+use axum::http::{HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Redirect, Response};
+use axum::{Form, Json};
+use axum_extra::extract::SignedCookieJar;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+pub async fn device_authorization(
+    services: State<Arc<Services>>,
+    authorization: Option<TokenHeader>,
+    Form(params): Form<DeviceAuthorizationParams>,
+) -> Result<Json<DeviceAuthorizationResponse>, AccessTokenError> {
+    let credentials = ClientCredentials::from_request(authorization.as_ref(), &params.client_auth)?;
+
+    Ok(Json(
+        services
+            .oauth2_service
+            .device_authorization(&credentials, &params)
+            .await?,
+    ))
+}
+
+#[derive(Deserialize)]
+pub struct DeviceQuery {
+    user_code: String,
+}
+
+/// What the verification page shows the user once they entered a user code.
+#[derive(Serialize)]
+pub struct DeviceDetails {
+    user_code: String,
+    client_name: String,
+    scopes: Vec<String>,
+}
+
+pub async fn device_request(
+    services: State<Arc<Services>>,
+    Query(query): Query<DeviceQuery>,
+) -> Result<Json<DeviceDetails>, Response> {
+    let (_, authorization) = services
+        .device_service
+        .find_by_user_code(&query.user_code)
+        .await
+        .map_err(IntoResponse::into_response)?
+        .filter(|(_, authorization)| matches!(authorization.status, DeviceStatus::Pending))
+        .ok_or((StatusCode::NOT_FOUND, "user code not found").into_response())?;
+
+    let client = services
+        .client_service
+        .get_by_client_id(&authorization.client_id)
+        .await
+        .map_err(IntoResponse::into_response)?
+        .ok_or((StatusCode::NOT_FOUND, "client not found").into_response())?;
+
+    Ok(Json(DeviceDetails {
+        user_code: DeviceService::display_user_code(&authorization.user_code),
+        client_name: client.display_name().to_string(),
+        scopes: authorization
+            .scope
+            .as_deref()
+            .map(scope::parse)
+            .unwrap_or_default()
+            .into_iter()
+            .map(ToString::to_string)
+            .collect(),
+    }))
+}
+
+#[derive(Deserialize)]
+pub struct VerifyForm {
+    user_code: String,
+    decision: String,
+}
+
+pub async fn verify(
+    services: State<Arc<Services>>,
+    headers: HeaderMap,
+    Form(req): Form<VerifyForm>,
+) -> Result<Redirect, Response> {
+    let device_uri = |key, value| {
+        let query = url::form_urlencoded::Serializer::new(String::new())
+            .append_pair(key, value)
+            .append_pair("user_code", &req.user_code)
+            .finish();
+        Redirect::to(&format!("/device?{}", query))
+    };
+
+    // the decision is made by the user logged in to this browser, like consent
+    let jar = services.session_service.cookie_jar(&headers);
+    let session = match jar.get(SessionService::COOKIE_NAME) {
+        Some(cookie) => services
+            .session_service
+            .get(cookie.value())
+            .await
+            .map_err(IntoResponse::into_response)?,
+        None => None,
+    };
+    let Some(session) = session else {
+        let query = url::form_urlencoded::Serializer::new(String::new())
+            .append_pair("user_code", &req.user_code)
+            .finish();
+        return Ok(Redirect::to(&format!("/device/login?{}", query)));
+    };
+
+    let Some((device_code, authorization)) = services
+        .device_service
+        .find_by_user_code(&req.user_code)
+        .await
+        .map_err(IntoResponse::into_response)?
+    else {
+        return Ok(device_uri("error", "invalid_user_code"));
+    };
+
+    let (status, client) = if req.decision == "approve" {
+        let client = services
+            .client_service
+            .get_by_client_id(&authorization.client_id)
+            .await
+            .map_err(IntoResponse::into_response)?
+            .ok_or((StatusCode::BAD_REQUEST, "client_id is invalid").into_response())?;
+
+        let status = DeviceStatus::Approved {
+            user_id: session.user_id,
+            auth_time: session.auth_time,
+        };
+        (status, Some(client))
+    } else {
+        (DeviceStatus::Denied, None)
+    };
+
+    if !services
+        .device_service
+        .decide(&device_code, status)
+        .await
+        .map_err(IntoResponse::into_response)?
+    {
+        return Ok(device_uri("error", "invalid_user_code"));
+    }
+
+    let result = match client {
+        Some(client) => {
+            services
+                .grant_service
+                .grant(session.user_id, &client, authorization.scope.as_deref())
+                .await
+                .map_err(IntoResponse::into_response)?;
+            "approved"
+        }
+        None => "denied",
+    };
+
+    tracing::info!(
+        client_id = authorization.client_id,
+        user.id = session.user_id.to_string(),
+        result,
+        "device authorization decided"
+    );
+    Ok(device_uri("result", result))
+}
+
+#[derive(Deserialize)]
+pub struct LoginForm {
+    username: String,
+    password: String,
+}
+
+/// Logs the user in to decide on a device authorization, then takes them back to the device
+/// page.
+pub async fn login(
+    services: State<Arc<Services>>,
+    headers: HeaderMap,
+    Query(query): Query<DeviceQuery>,
+    Form(req): Form<LoginForm>,
+) -> Result<(SignedCookieJar, Redirect), Response> {
+    let login_uri = |error| {
+        let query = url::form_urlencoded::Serializer::new(String::new())
+            .append_pair("error", error)
+            .append_pair("user_code", &query.user_code)
+            .finish();
+        Redirect::to(&format!("/device/login?{}", query)).into_response()
+    };
+
+    let user = match services
+        .user_service
+        .validate_and_return(&req.username, &req.password)
+        .await
+    {
+        Ok(user) => user,
+        Err(UserValidationError::UserNotFound | UserValidationError::InvalidPassword) => {
+            return Err(login_uri("invalid_credentials"))
+        }
+        Err(UserValidationError::NotActivated) => return Err(login_uri("not_activated")),
+        Err(UserValidationError::InternalError(_)) => {
+            return Err((StatusCode::INTERNAL_SERVER_ERROR, "").into_response())
+        }
+    };
+
+    let jar = services.session_service.cookie_jar(&headers);
+    let (jar, _) = start_session(&services, jar, user.id).await?;
+
+    let query = url::form_urlencoded::Serializer::new(String::new())
+        .append_pair("user_code", &query.user_code)
+        .finish();
+    Ok((jar, Redirect::to(&format!("/device?{}", query))))
+}
@@ -0,0 +1,85 @@
+use crate::services::grants::GrantWithClient;
+use crate::services::sessions::{Session, SessionService};
+use crate::Services;
+use axum::extract::State;
+use axum::http::{HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Redirect, Response};
+use axum::{Form, Json};
+use serde::Deserialize;
+use std::sync::Arc;
+
+/// Grants are only managed by the user themselves, from the browser they logged in with.
+async fn current_session(services: &Services, headers: &HeaderMap) -> Result<Session, Response> {
+    let not_logged_in = || (StatusCode::UNAUTHORIZED, "not logged in").into_response();
+
+    let jar = services.session_service.cookie_jar(headers);
+    let cookie = jar
+        .get(SessionService::COOKIE_NAME)
+        .ok_or_else(not_logged_in)?;
+
+    services
+        .session_service
+        .get(cookie.value())
+        .await
+        .map_err(IntoResponse::into_response)?
+        .ok_or_else(not_logged_in)
+}
+
+/// Clients the logged in user has granted access to.
+pub async fn list(
+    services: State<Arc<Services>>,
+    headers: HeaderMap,
+) -> Result<Json<Vec<GrantWithClient>>, Response> {
+    let session = current_session(&services, &headers).await?;
+
+    let grants = services
+        .grant_service
+        .list(session.user_id)
+        .await
+        .map_err(IntoResponse::into_response)?;
+
+    Ok(Json(grants))
+}
+
+#[derive(Deserialize)]
+pub struct RevokeForm {
+    client_id: String,
+}
+
+/// Withdraws the user's consent for a client and revokes the tokens it holds for the user.
+pub async fn revoke(
+    services: State<Arc<Services>>,
+    headers: HeaderMap,
+    Form(form): Form<RevokeForm>,
+) -> Result<Redirect, Response> {
+    let session = current_session(&services, &headers).await?;
+
+    let client = services
+        .client_service
+        .get_by_client_id(&form.client_id)
+        .await
+        .map_err(IntoResponse::into_response)?
+        .ok_or((StatusCode::NOT_FOUND, "grant not found").into_response())?;
+
+    if !services
+        .grant_service
+        .revoke(session.user_id, &client)
+        .await
+        .map_err(IntoResponse::into_response)?
+    {
+        return Err((StatusCode::NOT_FOUND, "grant not found").into_response());
+    }
+
+    services
+        .oauth2_service
+        .revoke_user_tokens(session.user_id, &client.client_id)
+        .await
+        .map_err(IntoResponse::into_response)?;
+    tracing::info!(
+        client_id = client.client_id,
+        user.id = session.user_id.to_string(),
+        "user revoked grant"
+    );
+
+    Ok(Redirect::to("/grants"))
+}
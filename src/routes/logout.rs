@@ -0,0 +1,179 @@
+use crate::services::sessions::{Session, SessionService};
+use crate::Services;
+use axum::extract::{Query, Request, State};
+use axum::http::{HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Redirect, Response};
+use axum::Form;
+use axum_extra::extract::SignedCookieJar;
+use serde::Deserialize;
+use std::sync::Arc;
+use tower::util::ServiceExt;
+use tower_http::services::ServeFile;
+use uuid::Uuid;
+
+/// Parameters of an RP-initiated logout request (OpenID Connect RP-Initiated Logout 1.0).
+#[derive(Deserialize)]
+pub struct LogoutParams {
+    id_token_hint: Option<String>,
+    client_id: Option<String>,
+    post_logout_redirect_uri: Option<String>,
+    state: Option<String>,
+}
+
+struct LogoutRequest {
+    /// User the RP asks to log out, known from the ID token hint.
+    user_id: Option<Uuid>,
+    redirect_uri: Option<String>,
+}
+
+impl LogoutRequest {
+    fn redirect(&self) -> Redirect {
+        Redirect::to(
+            self.redirect_uri
+                .as_deref()
+                .unwrap_or("/oauth2/logout?logged_out=true"),
+        )
+    }
+}
+
+async fn logout_request(
+    services: &Services,
+    params: &LogoutParams,
+) -> Result<LogoutRequest, Response> {
+    let hint = params
+        .id_token_hint
+        .as_deref()
+        .map(|hint| services.token_service.verify_id_token_hint(hint))
+        .transpose()
+        .map_err(|_| (StatusCode::BAD_REQUEST, "id_token_hint is invalid").into_response())?;
+
+    let client_id = match (&hint, &params.client_id) {
+        (Some(hint), Some(client_id)) if hint.aud != *client_id => {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                "client_id does not match id_token_hint",
+            )
+                .into_response())
+        }
+        (Some(hint), _) => Some(hint.aud.as_str()),
+        (None, client_id) => client_id.as_deref(),
+    };
+
+    let redirect_uri = match &params.post_logout_redirect_uri {
+        Some(uri) => {
+            let client = match client_id {
+                Some(client_id) => services
+                    .client_service
+                    .get_by_client_id(client_id)
+                    .await
+                    .map_err(IntoResponse::into_response)?,
+                None => None,
+            };
+            if !client.is_some_and(|client| client.is_post_logout_redirect_uri(uri)) {
+                tracing::info!(
+                    client_id,
+                    post_logout_redirect_uri = uri,
+                    "post_logout_redirect_uri is not registered for the client"
+                );
+                return Err(
+                    (StatusCode::BAD_REQUEST, "post_logout_redirect_uri mismatch").into_response(),
+                );
+            }
+
+            let mut url = url::Url::parse(uri).map_err(|e| {
+                (
+                    StatusCode::BAD_REQUEST,
+                    format!("invalid redirect url: {}", e),
+                )
+                    .into_response()
+            })?;
+            if let Some(state) = &params.state {
+                url.query_pairs_mut().append_pair("state", state);
+            }
+            Some(url.to_string())
+        }
+        None => None,
+    };
+
+    Ok(LogoutRequest {
+        user_id: hint.and_then(|hint| hint.sub.parse().ok()),
+        redirect_uri,
+    })
+}
+
+async fn current_session(
+    services: &Services,
+    jar: &SignedCookieJar,
+) -> Result<Option<(String, Session)>, Response> {
+    let Some(cookie) = jar.get(SessionService::COOKIE_NAME) else {
+        return Ok(None);
+    };
+    let session_id = cookie.value().to_string();
+
+    Ok(services
+        .session_service
+        .get(&session_id)
+        .await
+        .map_err(IntoResponse::into_response)?
+        .map(|session| (session_id, session)))
+}
+
+/// Ends the session and notifies the clients it was used with over the back channel.
+async fn end_session(
+    services: &Services,
+    jar: SignedCookieJar,
+    session_id: &str,
+    session: &Session,
+) -> Result<SignedCookieJar, Response> {
+    let client_ids = services
+        .session_service
+        .end(session_id, session)
+        .await
+        .map_err(IntoResponse::into_response)?;
+    services
+        .backchannel_logout_service
+        .notify(session, client_ids);
+
+    Ok(jar.remove(services.session_service.removal_cookie()))
+}
+
+/// End session endpoint. The session is ended right away when the ID token hint shows the
+/// request comes from the logged in user's RP, otherwise the user is asked to confirm.
+pub async fn logout(
+    services: State<Arc<Services>>,
+    Query(params): Query<LogoutParams>,
+    request: Request,
+) -> Result<Response, Response> {
+    let logout = logout_request(&services, &params).await?;
+    let jar = services.session_service.cookie_jar(request.headers());
+
+    match current_session(&services, &jar).await? {
+        Some((session_id, session)) if logout.user_id == Some(session.user_id) => {
+            let jar = end_session(&services, jar, &session_id, &session).await?;
+            tracing::info!(user.id = session.user_id.to_string(), "user logged out");
+            Ok((jar, logout.redirect()).into_response())
+        }
+        None if logout.redirect_uri.is_some() => Ok(logout.redirect().into_response()),
+        _ => Ok(ServeFile::new("static/logout.html")
+            .oneshot(request)
+            .await
+            .into_response()),
+    }
+}
+
+/// Logout confirmed by the user on the logout page.
+pub async fn confirm(
+    services: State<Arc<Services>>,
+    headers: HeaderMap,
+    Form(params): Form<LogoutParams>,
+) -> Result<(SignedCookieJar, Redirect), Response> {
+    let logout = logout_request(&services, &params).await?;
+    let mut jar = services.session_service.cookie_jar(&headers);
+
+    if let Some((session_id, session)) = current_session(&services, &jar).await? {
+        jar = end_session(&services, jar, &session_id, &session).await?;
+        tracing::info!(user.id = session.user_id.to_string(), "user logged out");
+    }
+
+    Ok((jar, logout.redirect()))
+}
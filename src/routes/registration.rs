@@ -0,0 +1,85 @@
+use crate::helpers::{TokenHeader, Validatable};
+use crate::services::clients::metadata::ClientMetadata;
+use crate::services::clients::{Client, ClientInformation, RegistrationError};
+use crate::Services;
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::Json;
+use std::sync::Arc;
+
+fn bearer_token(token: &Option<TokenHeader>) -> Result<&str, RegistrationError> {
+    token
+        .as_ref()
+        .ok_or(RegistrationError::MissingToken)?
+        .to_bearer_token()
+        .map_err(|_| RegistrationError::InvalidToken)
+}
+
+async fn authenticate(
+    services: &Services,
+    token: &Option<TokenHeader>,
+    client_id: &str,
+) -> Result<Client, RegistrationError> {
+    services
+        .client_service
+        .authenticate_registration(client_id, bearer_token(token)?)
+        .await
+}
+
+/// Registers a client, for callers holding the initial access token.
+pub async fn register(
+    services: State<Arc<Services>>,
+    token: Option<TokenHeader>,
+    Json(metadata): Json<ClientMetadata>,
+) -> Result<(StatusCode, Json<ClientInformation>), RegistrationError> {
+    if !services
+        .client_service
+        .is_initial_access_token(bearer_token(&token)?)
+    {
+        return Err(RegistrationError::InvalidToken);
+    }
+    metadata.validate()?;
+
+    let information = services.client_service.register(metadata).await?;
+
+    Ok((StatusCode::CREATED, Json(information)))
+}
+
+pub async fn read(
+    services: State<Arc<Services>>,
+    token: Option<TokenHeader>,
+    Path(client_id): Path<String>,
+) -> Result<Json<ClientInformation>, RegistrationError> {
+    let client = authenticate(&services, &token, &client_id).await?;
+
+    Ok(Json(services.client_service.read(&client).await?))
+}
+
+pub async fn update(
+    services: State<Arc<Services>>,
+    token: Option<TokenHeader>,
+    Path(client_id): Path<String>,
+    Json(metadata): Json<ClientMetadata>,
+) -> Result<Json<ClientInformation>, RegistrationError> {
+    let client = authenticate(&services, &token, &client_id).await?;
+    metadata.validate()?;
+
+    Ok(Json(
+        services.client_service.update(&client, metadata).await?,
+    ))
+}
+
+pub async fn delete(
+    services: State<Arc<Services>>,
+    token: Option<TokenHeader>,
+    Path(client_id): Path<String>,
+) -> Result<StatusCode, RegistrationError> {
+    let client = authenticate(&services, &token, &client_id).await?;
+    let family_ids = services.client_service.delete(&client).await?;
+    services
+        .oauth2_service
+        .revoke_access_tokens(family_ids)
+        .await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -0,0 +1,14 @@
+use crate::services::discovery::ProviderMetadata;
+use crate::Services;
+use axum::extract::State;
+use axum::Json;
+use jsonwebtoken::jwk::JwkSet;
+use std::sync::Arc;
+
+pub async fn openid_configuration(services: State<Arc<Services>>) -> Json<ProviderMetadata> {
+    Json(services.discovery_service.provider_metadata())
+}
+
+pub async fn jwks(services: State<Arc<Services>>) -> Json<JwkSet> {
+    Json(services.token_service.jwks())
+}
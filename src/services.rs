@@ -1,6 +1,16 @@
+pub mod backchannel_logout;
 pub mod clients;
+pub mod devices;
+pub mod discovery;
 pub mod email;
+pub mod grants;
 pub mod oauth2;
+pub mod pushed_authorizations;
 pub mod rate_limit;
+pub mod refresh_tokens;
+pub mod resource_servers;
+pub mod sessions;
+pub mod signing_keys;
+pub mod token_exchange_policies;
 pub mod tokens;
 pub mod users;
@@ -0,0 +1,100 @@
+use crate::services::clients::ClientService;
+use crate::services::sessions::Session;
+use crate::services::tokens::TokenService;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Notifies clients that a session they were used with ended, by posting a logout token to their
+/// back-channel logout uri (OpenID Connect Back-Channel Logout 1.0). Deliveries run in the
+/// background, so a slow or unreachable client never holds up the user's logout.
+pub struct BackchannelLogoutService {
+    token_service: Arc<TokenService>,
+    client_service: Arc<ClientService>,
+    http_client: reqwest::Client,
+    max_attempts: u32,
+}
+
+impl BackchannelLogoutService {
+    pub fn new(token_service: Arc<TokenService>, client_service: Arc<ClientService>) -> Self {
+        Self {
+            token_service,
+            client_service,
+            http_client: reqwest::Client::builder()
+                .timeout(Duration::from_secs(5))
+                .redirect(reqwest::redirect::Policy::none())
+                .build()
+                .expect("failed to build http client"),
+            max_attempts: 5,
+        }
+    }
+}
+
+impl BackchannelLogoutService {
+    /// Sends a logout token for `session` to each of `client_ids` that registered a back-channel
+    /// logout uri.
+    pub fn notify(self: &Arc<Self>, session: &Session, client_ids: Vec<String>) {
+        for client_id in client_ids {
+            let service = self.clone();
+            let user_id = session.user_id;
+            let sid = session.sid.clone();
+            tokio::spawn(async move { service.deliver(&client_id, user_id, &sid).await });
+        }
+    }
+
+    async fn deliver(&self, client_id: &str, user_id: uuid::Uuid, sid: &str) {
+        let client = match self.client_service.get_by_client_id(client_id).await {
+            Ok(Some(client)) => client,
+            Ok(None) => return,
+            Err(error) => {
+                tracing::error!(client_id, error = %error, "failed to load client for back-channel logout");
+                return;
+            }
+        };
+        let Some(uri) = client.backchannel_logout_uri() else {
+            return;
+        };
+
+        let logout_token = match self
+            .token_service
+            .create_logout_token(client_id, user_id, sid)
+        {
+            Ok(token) => token,
+            Err(error) => {
+                tracing::error!(client_id, error = %error, "failed to create logout token");
+                return;
+            }
+        };
+
+        let mut backoff = Duration::from_secs(1);
+        for attempt in 1..=self.max_attempts {
+            let result = self
+                .http_client
+                .post(uri)
+                .form(&[("logout_token", &logout_token)])
+                .send()
+                .await
+                .and_then(reqwest::Response::error_for_status);
+
+            match result {
+                Ok(_) => {
+                    tracing::info!(client_id, attempt, "back-channel logout delivered");
+                    return;
+                }
+                Err(error) => {
+                    tracing::warn!(client_id, attempt, error = %error, "back-channel logout failed");
+                }
+            }
+
+            if attempt < self.max_attempts {
+                tokio::time::sleep(backoff).await;
+                backoff *= 2;
+            }
+        }
+
+        tracing::error!(
+            client_id,
+            attempts = self.max_attempts,
+            "back-channel logout abandoned"
+        );
+    }
+}
@@ -1,16 +1,69 @@
+pub mod metadata;
+
 use crate::db::DbPool;
-use crate::helpers::InternalError;
-use std::sync::Arc;
+use crate::helpers::{random_token, InternalError};
+use crate::services::clients::metadata::ClientMetadata;
+use crate::services::oauth2::OauthErrorResponse;
+use axum::http::{header, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use jsonwebtoken::jwk::JwkSet;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+use uuid::Uuid;
 
 pub use models::Client;
 
 pub struct ClientService {
     pool: Arc<DbPool>,
+    registration_endpoint: String,
+    initial_access_token: Option<String>,
+    http_client: reqwest::Client,
+    /// Key sets fetched from `jwks_uri`s, with the time they were fetched.
+    jwks_cache: RwLock<HashMap<String, (Instant, JwkSet)>>,
+}
+
+/// Client information response, as described in RFC 7591 section 3.2.1 and RFC 7592 section 3.
+#[derive(Serialize)]
+pub struct ClientInformation {
+    client_id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    client_secret: Option<String>,
+    client_id_issued_at: i64,
+    client_secret_expires_at: i64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    registration_access_token: Option<String>,
+    registration_client_uri: String,
+    #[serde(flatten)]
+    metadata: ClientMetadata,
 }
 
 impl ClientService {
-    pub fn new(pool: Arc<DbPool>) -> Self {
-        Self { pool }
+    /// How long fetched key sets are used before they are fetched again, so that clients can
+    /// rotate their keys.
+    const JWKS_CACHE_TTL: Duration = Duration::from_secs(5 * 60);
+
+    pub fn new(pool: Arc<DbPool>, registration_endpoint: String) -> Self {
+        Self {
+            pool,
+            registration_endpoint,
+            initial_access_token: None,
+            http_client: reqwest::Client::builder()
+                .timeout(Duration::from_secs(5))
+                .redirect(reqwest::redirect::Policy::none())
+                .build()
+                .expect("failed to build http client"),
+            jwks_cache: RwLock::new(HashMap::new()),
+        }
+    }
+
+    /// Enables dynamic client registration for callers presenting `token`.
+    pub fn set_initial_access_token(mut self, token: Option<String>) -> Self {
+        self.initial_access_token = token;
+        self
     }
 }
 
@@ -21,38 +74,444 @@ impl ClientService {
             .await
             .map_err(Into::into)
     }
+
+    /// Scopes the client is permitted to request.
+    pub async fn allowed_scopes(&self, client: &Client) -> Result<Vec<String>, InternalError> {
+        let mut conn = self.pool.get().await?;
+        client.find_scopes(&mut conn).await.map_err(Into::into)
+    }
+
+    pub fn is_initial_access_token(&self, token: &str) -> bool {
+        // compare digests so that the comparison time says nothing about the token
+        self.initial_access_token
+            .as_deref()
+            .is_some_and(|expected| Sha256::digest(expected) == Sha256::digest(token))
+    }
+
+    pub async fn register(
+        &self,
+        metadata: ClientMetadata,
+    ) -> Result<ClientInformation, InternalError> {
+        let client_secret = metadata.uses_client_secret().then(random_token);
+        let registration_access_token = random_token();
+
+        let mut conn = self.pool.get().await?;
+        let client = models::NewClient::new(
+            Uuid::new_v4().to_string(),
+            client_secret
+                .as_deref()
+                .map(models::hash_secret)
+                .transpose()?,
+            models::hash_secret(&registration_access_token)?,
+            &metadata,
+        )
+        .save(&metadata.scopes(), &mut conn)
+        .await?;
+        tracing::info!(client.client_id, "client registered");
+
+        Ok(ClientInformation {
+            client_secret,
+            registration_access_token: Some(registration_access_token),
+            ..self.read(&client).await?
+        })
+    }
+
+    /// Finds the client managed with the registration access `token`.
+    pub async fn authenticate_registration(
+        &self,
+        client_id: &str,
+        token: &str,
+    ) -> Result<Client, RegistrationError> {
+        let client = self
+            .get_by_client_id(client_id)
+            .await?
+            .ok_or(RegistrationError::InvalidToken)?;
+
+        if !client.is_registration_token_match(token)? {
+            tracing::warn!(client.client_id, "mismatch registration access token");
+            return Err(RegistrationError::InvalidToken);
+        }
+
+        Ok(client)
+    }
+
+    pub async fn read(&self, client: &Client) -> Result<ClientInformation, InternalError> {
+        let scopes = self.allowed_scopes(client).await?;
+
+        Ok(ClientInformation {
+            client_id: client.client_id.clone(),
+            client_secret: None,
+            client_id_issued_at: client.issued_at(),
+            // secrets do not expire
+            client_secret_expires_at: 0,
+            registration_access_token: None,
+            registration_client_uri: format!("{}/{}", self.registration_endpoint, client.client_id),
+            metadata: client.metadata(&scopes),
+        })
+    }
+
+    /// Replaces the metadata of the client, as described in RFC 7592 section 2.2.
+    pub async fn update(
+        &self,
+        client: &Client,
+        metadata: ClientMetadata,
+    ) -> Result<ClientInformation, RegistrationError> {
+        if metadata
+            .client_id
+            .as_ref()
+            .is_some_and(|client_id| *client_id != client.client_id)
+        {
+            return Err(RegistrationError::InvalidClientMetadata(
+                "client_id does not match",
+            ));
+        }
+
+        // a secret is only kept while the client authenticates with it
+        let mut changeset = models::ClientChangeset::new(&metadata);
+        let mut client_secret = None;
+        if !metadata.uses_client_secret() {
+            changeset = changeset.set_client_secret(None);
+        } else if !client.has_secret() {
+            let secret = random_token();
+            changeset = changeset.set_client_secret(Some(models::hash_secret(&secret)?));
+            client_secret = Some(secret);
+        }
+
+        let mut conn = self.pool.get().await?;
+        let client = changeset
+            .save(client.id, &metadata.scopes(), &mut conn)
+            .await?;
+        tracing::info!(client.client_id, "client updated");
+
+        Ok(ClientInformation {
+            client_secret,
+            ..self.read(&client).await?
+        })
+    }
+
+    /// Keys the client signs its assertions with, registered inline or published at its
+    /// `jwks_uri`. `None` when the client has no usable keys.
+    pub async fn jwks(&self, client: &Client) -> Option<JwkSet> {
+        if let Some(jwks) = client.jwks() {
+            return serde_json::from_value(jwks.clone())
+                .inspect_err(|e| tracing::warn!(client.client_id, error = %e, "malformed jwks"))
+                .ok();
+        }
+
+        let jwks_uri = client.jwks_uri()?;
+        if let Some((fetched_at, jwks)) = self
+            .jwks_cache
+            .read()
+            .expect("jwks cache lock poisoned")
+            .get(jwks_uri)
+        {
+            if fetched_at.elapsed() < Self::JWKS_CACHE_TTL {
+                return Some(jwks.clone());
+            }
+        }
+
+        let response = self
+            .http_client
+            .get(jwks_uri)
+            .send()
+            .await
+            .and_then(reqwest::Response::error_for_status);
+        let jwks: JwkSet = match response {
+            Ok(response) => response
+                .json()
+                .await
+                .inspect_err(|e| tracing::warn!(client.client_id, error = %e, "malformed jwks"))
+                .ok()?,
+            Err(e) => {
+                tracing::warn!(client.client_id, jwks_uri, error = %e, "failed to fetch jwks");
+                return None;
+            }
+        };
+        self.jwks_cache
+            .write()
+            .expect("jwks cache lock poisoned")
+            .insert(jwks_uri.to_string(), (Instant::now(), jwks.clone()));
+
+        Some(jwks)
+    }
+
+    /// Fetches a request object (RFC 9101 section 5.2) from one of the client's registered
+    /// request uris. `None` when the uri is not registered or cannot be fetched.
+    pub async fn fetch_request_object(&self, client: &Client, request_uri: &str) -> Option<String> {
+        if !client.is_request_uri(request_uri) {
+            tracing::info!(
+                client.client_id,
+                request_uri,
+                "request_uri is not registered"
+            );
+            return None;
+        }
+
+        let response = self
+            .http_client
+            .get(request_uri)
+            .header(header::ACCEPT, "application/oauth-authz-req+jwt")
+            .send()
+            .await
+            .and_then(reqwest::Response::error_for_status);
+        match response {
+            Ok(response) => response
+                .text()
+                .await
+                .inspect_err(|e| {
+                    tracing::warn!(client.client_id, request_uri, error = %e, "failed to read request object")
+                })
+                .ok()
+                .map(|request| request.trim().to_string()),
+            Err(e) => {
+                tracing::warn!(client.client_id, request_uri, error = %e, "failed to fetch request object");
+                None
+            }
+        }
+    }
+
+    /// Deletes the client along with its refresh token families, returning the ids of the
+    /// families so the access tokens derived from them can be revoked too.
+    pub async fn delete(&self, client: &Client) -> Result<Vec<Uuid>, InternalError> {
+        let mut conn = self.pool.get().await?;
+        let family_ids = client.delete(&mut conn).await?;
+        tracing::info!(client.client_id, "client deleted");
+
+        Ok(family_ids)
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum RegistrationError {
+    #[error("invalid redirect uri: {0}")]
+    InvalidRedirectUri(&'static str),
+    #[error("invalid client metadata: {0}")]
+    InvalidClientMetadata(&'static str),
+    #[error("missing access token")]
+    MissingToken,
+    #[error("invalid access token")]
+    InvalidToken,
+    #[error("internal error: {0}")]
+    InternalError(InternalError),
+}
+
+impl<T: Into<InternalError>> From<T> for RegistrationError {
+    fn from(error: T) -> Self {
+        RegistrationError::InternalError(error.into())
+    }
+}
+
+impl IntoResponse for RegistrationError {
+    fn into_response(self) -> Response {
+        match self {
+            RegistrationError::InvalidRedirectUri(description) => (
+                StatusCode::BAD_REQUEST,
+                Json(OauthErrorResponse {
+                    error: "invalid_redirect_uri",
+                    error_description: Some(description),
+                }),
+            )
+                .into_response(),
+            RegistrationError::InvalidClientMetadata(description) => (
+                StatusCode::BAD_REQUEST,
+                Json(OauthErrorResponse {
+                    error: "invalid_client_metadata",
+                    error_description: Some(description),
+                }),
+            )
+                .into_response(),
+            RegistrationError::MissingToken => (
+                StatusCode::UNAUTHORIZED,
+                [(header::WWW_AUTHENTICATE, "Bearer")],
+            )
+                .into_response(),
+            RegistrationError::InvalidToken => (
+                StatusCode::UNAUTHORIZED,
+                [(header::WWW_AUTHENTICATE, r#"Bearer error="invalid_token""#)],
+            )
+                .into_response(),
+            RegistrationError::InternalError(e) => e.into_response(),
+        }
+    }
 }
 
 mod models {
-    use crate::db::schema::clients;
-    use argon2::PasswordVerifier;
+    use crate::db::schema::{client_scopes, clients};
+    use crate::services::clients::metadata::ClientMetadata;
+    use crate::services::oauth2::scope;
+    use crate::services::refresh_tokens::RefreshTokenFamily;
+    use argon2::password_hash::rand_core::OsRng;
+    use argon2::password_hash::SaltString;
+    use argon2::{PasswordHasher, PasswordVerifier};
+    use chrono::NaiveDateTime;
     use diesel::{
-        ExpressionMethods, OptionalExtension, QueryDsl, Queryable, Selectable, SelectableHelper,
+        AsChangeset, ExpressionMethods, Insertable, OptionalExtension, QueryDsl, Queryable,
+        Selectable, SelectableHelper,
     };
-    use diesel_async::{AsyncPgConnection, RunQueryDsl};
+    use diesel_async::scoped_futures::ScopedFutureExt;
+    use diesel_async::{AsyncConnection, AsyncPgConnection, RunQueryDsl};
+    use uuid::Uuid;
 
     #[derive(Debug, Selectable, Queryable)]
     pub struct Client {
-        client_secret: String,
-        pub redirect_uri: String,
+        pub id: Uuid,
+        pub client_id: String,
+        client_secret: Option<String>,
+        pub require_pkce: bool,
+        name: Option<String>,
+        pub skip_consent: bool,
+        redirect_uris: Vec<Option<String>>,
+        grant_types: Vec<Option<String>>,
+        pub token_endpoint_auth_method: String,
+        logo_uri: Option<String>,
+        registration_access_token: Option<String>,
+        created_at: Option<NaiveDateTime>,
+        post_logout_redirect_uris: Vec<Option<String>>,
+        backchannel_logout_uri: Option<String>,
+        jwks: Option<serde_json::Value>,
+        jwks_uri: Option<String>,
+        pub require_pushed_authorization_requests: bool,
+        request_uris: Vec<Option<String>>,
+        pub require_signed_request_object: bool,
+    }
+
+    pub fn hash_secret(secret: &str) -> Result<String, argon2::password_hash::Error> {
+        let salt = SaltString::generate(&mut OsRng);
+        argon2::Argon2::default()
+            .hash_password(secret.as_bytes(), &salt)
+            .map(|hash| hash.to_string())
+    }
+
+    fn verify_secret(secret: &str, hash: &str) -> Result<bool, argon2::password_hash::Error> {
+        let argon2 = argon2::Argon2::default();
+        let parsed_hash = argon2::PasswordHash::new(hash)?;
+
+        match argon2.verify_password(secret.as_bytes(), &parsed_hash) {
+            Ok(_) => Ok(true),
+            Err(error) => {
+                tracing::debug!(
+                    error = error.to_string(),
+                    "client secret verification failed"
+                );
+                Ok(false)
+            }
+        }
     }
 
     impl Client {
-        pub fn is_secret_match(&self, secret: &str) -> Result<bool, argon2::password_hash::Error> {
-            let argon2 = argon2::Argon2::default();
-            let parsed_hash = argon2::PasswordHash::new(&self.client_secret)?;
-
-            match argon2.verify_password(secret.as_bytes(), &parsed_hash) {
-                Ok(_) => Ok(true),
-                Err(error) => {
-                    tracing::debug!(
-                        error = error.to_string(),
-                        "client secret verification failed"
-                    );
-                    Ok(false)
+        /// Name shown to users, falling back to the client id.
+        pub fn display_name(&self) -> &str {
+            self.name.as_deref().unwrap_or(&self.client_id)
+        }
+
+        pub fn redirect_uris(&self) -> impl Iterator<Item = &str> {
+            self.redirect_uris.iter().flatten().map(String::as_str)
+        }
+
+        pub fn is_post_logout_redirect_uri(&self, uri: &str) -> bool {
+            self.post_logout_redirect_uris
+                .iter()
+                .flatten()
+                .any(|registered| registered == uri)
+        }
+
+        pub fn backchannel_logout_uri(&self) -> Option<&str> {
+            self.backchannel_logout_uri.as_deref()
+        }
+
+        /// Whether request objects may be fetched from `uri`. A fragment only tells versions of
+        /// the request object apart.
+        pub fn is_request_uri(&self, uri: &str) -> bool {
+            let uri = uri.split_once('#').map_or(uri, |(uri, _)| uri);
+            self.request_uris
+                .iter()
+                .flatten()
+                .any(|registered| registered == uri)
+        }
+
+        pub fn grant_types(&self) -> impl Iterator<Item = &str> {
+            self.grant_types.iter().flatten().map(String::as_str)
+        }
+
+        pub fn allows_grant_type(&self, grant_type: &str) -> bool {
+            self.grant_types().any(|allowed| allowed == grant_type)
+        }
+
+        /// Picks the redirect uri of an authorization request: `requested` when it exactly matches
+        /// a registered uri, or the registered uri when the client has only one.
+        pub fn resolve_redirect_uri(&self, requested: Option<&str>) -> Option<&str> {
+            match requested {
+                Some(requested) => self.redirect_uris().find(|uri| *uri == requested),
+                None => {
+                    let mut uris = self.redirect_uris();
+                    match (uris.next(), uris.next()) {
+                        (Some(uri), None) => Some(uri),
+                        _ => None,
+                    }
                 }
             }
         }
+
+        /// Clients authenticating with `private_key_jwt` have no secret.
+        pub fn is_secret_match(&self, secret: &str) -> Result<bool, argon2::password_hash::Error> {
+            match &self.client_secret {
+                Some(hash) => verify_secret(secret, hash),
+                None => Ok(false),
+            }
+        }
+
+        pub fn has_secret(&self) -> bool {
+            self.client_secret.is_some()
+        }
+
+        pub fn jwks(&self) -> Option<&serde_json::Value> {
+            self.jwks.as_ref()
+        }
+
+        pub fn jwks_uri(&self) -> Option<&str> {
+            self.jwks_uri.as_deref()
+        }
+
+        /// Clients that were not dynamically registered have no registration access token.
+        pub fn is_registration_token_match(
+            &self,
+            token: &str,
+        ) -> Result<bool, argon2::password_hash::Error> {
+            match &self.registration_access_token {
+                Some(hash) => verify_secret(token, hash),
+                None => Ok(false),
+            }
+        }
+
+        pub fn issued_at(&self) -> i64 {
+            self.created_at
+                .map(|created_at| created_at.and_utc().timestamp())
+                .unwrap_or_default()
+        }
+
+        pub fn metadata(&self, scopes: &[String]) -> ClientMetadata {
+            ClientMetadata {
+                redirect_uris: self.redirect_uris().map(ToString::to_string).collect(),
+                post_logout_redirect_uris: self
+                    .post_logout_redirect_uris
+                    .iter()
+                    .flatten()
+                    .cloned()
+                    .collect(),
+                backchannel_logout_uri: self.backchannel_logout_uri.clone(),
+                jwks: self.jwks.clone(),
+                jwks_uri: self.jwks_uri.clone(),
+                require_pushed_authorization_requests: self.require_pushed_authorization_requests,
+                request_uris: self.request_uris.iter().flatten().cloned().collect(),
+                require_signed_request_object: self.require_signed_request_object,
+                grant_types: self.grant_types().map(ToString::to_string).collect(),
+                token_endpoint_auth_method: self.token_endpoint_auth_method.clone(),
+                client_name: self.name.clone(),
+                logo_uri: self.logo_uri.clone(),
+                scope: scope::join(&scopes.iter().map(String::as_str).collect::<Vec<_>>()),
+                client_id: None,
+            }
+        }
     }
 
     impl Client {
@@ -66,7 +525,214 @@ mod models {
                 .first(conn)
                 .await
                 .optional()
-                .map_err(Into::into)
+        }
+
+        pub async fn find_scopes(
+            &self,
+            conn: &mut AsyncPgConnection,
+        ) -> Result<Vec<String>, diesel::result::Error> {
+            client_scopes::table
+                .select(client_scopes::scope)
+                .filter(client_scopes::client_id.eq(self.id))
+                .load(conn)
+                .await
+        }
+
+        /// Deletes the client and revokes its refresh token families, returning their ids.
+        pub async fn delete(
+            &self,
+            conn: &mut AsyncPgConnection,
+        ) -> Result<Vec<Uuid>, diesel::result::Error> {
+            conn.transaction(|conn| {
+                async move {
+                    diesel::delete(clients::table)
+                        .filter(clients::id.eq(self.id))
+                        .execute(conn)
+                        .await?;
+
+                    RefreshTokenFamily::revoke_client(&self.client_id, conn).await
+                }
+                .scope_boxed()
+            })
+            .await
+        }
+    }
+
+    async fn replace_scopes(
+        id: Uuid,
+        scopes: &[&str],
+        conn: &mut AsyncPgConnection,
+    ) -> Result<(), diesel::result::Error> {
+        diesel::delete(client_scopes::table)
+            .filter(client_scopes::client_id.eq(id))
+            .execute(conn)
+            .await?;
+        if scopes.is_empty() {
+            return Ok(());
+        }
+        diesel::insert_into(client_scopes::table)
+            .values(
+                scopes
+                    .iter()
+                    .map(|scope| {
+                        (
+                            client_scopes::client_id.eq(id),
+                            client_scopes::scope.eq(*scope),
+                        )
+                    })
+                    .collect::<Vec<_>>(),
+            )
+            .execute(conn)
+            .await?;
+
+        Ok(())
+    }
+
+    #[derive(Debug, Insertable)]
+    #[diesel(table_name = clients)]
+    pub struct NewClient {
+        client_id: String,
+        client_secret: Option<String>,
+        name: Option<String>,
+        redirect_uris: Vec<Option<String>>,
+        post_logout_redirect_uris: Vec<Option<String>>,
+        backchannel_logout_uri: Option<String>,
+        jwks: Option<serde_json::Value>,
+        jwks_uri: Option<String>,
+        require_pushed_authorization_requests: bool,
+        request_uris: Vec<Option<String>>,
+        require_signed_request_object: bool,
+        grant_types: Vec<Option<String>>,
+        token_endpoint_auth_method: String,
+        logo_uri: Option<String>,
+        registration_access_token: Option<String>,
+    }
+
+    impl NewClient {
+        pub fn new(
+            client_id: String,
+            client_secret: Option<String>,
+            registration_access_token: String,
+            metadata: &ClientMetadata,
+        ) -> Self {
+            Self {
+                client_id,
+                client_secret,
+                name: metadata.client_name.clone(),
+                redirect_uris: metadata.redirect_uris.iter().cloned().map(Some).collect(),
+                post_logout_redirect_uris: metadata
+                    .post_logout_redirect_uris
+                    .iter()
+                    .cloned()
+                    .map(Some)
+                    .collect(),
+                backchannel_logout_uri: metadata.backchannel_logout_uri.clone(),
+                jwks: metadata.jwks.clone(),
+                jwks_uri: metadata.jwks_uri.clone(),
+                require_pushed_authorization_requests: metadata
+                    .require_pushed_authorization_requests,
+                request_uris: metadata.request_uris.iter().cloned().map(Some).collect(),
+                require_signed_request_object: metadata.require_signed_request_object,
+                grant_types: metadata.grant_types.iter().cloned().map(Some).collect(),
+                token_endpoint_auth_method: metadata.token_endpoint_auth_method.clone(),
+                logo_uri: metadata.logo_uri.clone(),
+                registration_access_token: Some(registration_access_token),
+            }
+        }
+
+        pub async fn save(
+            self,
+            scopes: &[&str],
+            conn: &mut AsyncPgConnection,
+        ) -> Result<Client, diesel::result::Error> {
+            conn.transaction(|conn| {
+                async move {
+                    let client: Client = diesel::insert_into(clients::table)
+                        .values(self)
+                        .returning(Client::as_select())
+                        .get_result(conn)
+                        .await?;
+                    replace_scopes(client.id, scopes, conn).await?;
+
+                    Ok(client)
+                }
+                .scope_boxed()
+            })
+            .await
+        }
+    }
+
+    #[derive(Debug, AsChangeset)]
+    #[diesel(table_name = clients, treat_none_as_null = true)]
+    pub struct ClientChangeset {
+        name: Option<String>,
+        redirect_uris: Vec<Option<String>>,
+        post_logout_redirect_uris: Vec<Option<String>>,
+        backchannel_logout_uri: Option<String>,
+        jwks: Option<serde_json::Value>,
+        jwks_uri: Option<String>,
+        require_pushed_authorization_requests: bool,
+        request_uris: Vec<Option<String>>,
+        require_signed_request_object: bool,
+        grant_types: Vec<Option<String>>,
+        token_endpoint_auth_method: String,
+        logo_uri: Option<String>,
+        /// Left unchanged when `None`.
+        #[diesel(treat_none_as_null = false)]
+        client_secret: Option<Option<String>>,
+    }
+
+    impl ClientChangeset {
+        pub fn new(metadata: &ClientMetadata) -> Self {
+            Self {
+                name: metadata.client_name.clone(),
+                redirect_uris: metadata.redirect_uris.iter().cloned().map(Some).collect(),
+                post_logout_redirect_uris: metadata
+                    .post_logout_redirect_uris
+                    .iter()
+                    .cloned()
+                    .map(Some)
+                    .collect(),
+                backchannel_logout_uri: metadata.backchannel_logout_uri.clone(),
+                jwks: metadata.jwks.clone(),
+                jwks_uri: metadata.jwks_uri.clone(),
+                require_pushed_authorization_requests: metadata
+                    .require_pushed_authorization_requests,
+                request_uris: metadata.request_uris.iter().cloned().map(Some).collect(),
+                require_signed_request_object: metadata.require_signed_request_object,
+                grant_types: metadata.grant_types.iter().cloned().map(Some).collect(),
+                token_endpoint_auth_method: metadata.token_endpoint_auth_method.clone(),
+                logo_uri: metadata.logo_uri.clone(),
+                client_secret: None,
+            }
+        }
+
+        pub fn set_client_secret(mut self, client_secret: Option<String>) -> Self {
+            self.client_secret = Some(client_secret);
+            self
+        }
+
+        pub async fn save(
+            self,
+            id: Uuid,
+            scopes: &[&str],
+            conn: &mut AsyncPgConnection,
+        ) -> Result<Client, diesel::result::Error> {
+            conn.transaction(|conn| {
+                async move {
+                    let client: Client = diesel::update(clients::table)
+                        .filter(clients::id.eq(id))
+                        .set(self)
+                        .returning(Client::as_select())
+                        .get_result(conn)
+                        .await?;
+                    replace_scopes(client.id, scopes, conn).await?;
+
+                    Ok(client)
+                }
+                .scope_boxed()
+            })
+            .await
         }
     }
 }
@@ -0,0 +1,378 @@
+use crate::helpers::Validatable;
+use crate::services::clients::RegistrationError;
+use crate::services::oauth2::{scope, Oauth2Service};
+use jsonwebtoken::jwk::JwkSet;
+use serde::{Deserialize, Serialize};
+use url::{Host, Url};
+
+/// Client metadata, as described in RFC 7591 section 2.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct ClientMetadata {
+    #[serde(default)]
+    pub redirect_uris: Vec<String>,
+    #[serde(default)]
+    pub post_logout_redirect_uris: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub backchannel_logout_uri: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub jwks: Option<serde_json::Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub jwks_uri: Option<String>,
+    /// Whether authorization requests must be pushed (RFC 9126 section 6).
+    #[serde(default)]
+    pub require_pushed_authorization_requests: bool,
+    /// Uris request objects may be fetched from (OpenID Connect Dynamic Client Registration 1.0).
+    #[serde(default)]
+    pub request_uris: Vec<String>,
+    /// Whether authorization requests must be signed request objects (RFC 9101 section 10.5).
+    #[serde(default)]
+    pub require_signed_request_object: bool,
+    #[serde(default = "default_grant_types")]
+    pub grant_types: Vec<String>,
+    #[serde(default = "default_token_endpoint_auth_method")]
+    pub token_endpoint_auth_method: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub client_name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub logo_uri: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scope: Option<String>,
+    /// Only checked against the client being updated.
+    #[serde(default, skip_serializing)]
+    pub client_id: Option<String>,
+}
+
+fn default_grant_types() -> Vec<String> {
+    vec![
+        "authorization_code".to_string(),
+        "refresh_token".to_string(),
+    ]
+}
+
+fn default_token_endpoint_auth_method() -> String {
+    "client_secret_post".to_string()
+}
+
+fn is_web_url(url: &str) -> bool {
+    Url::parse(url).is_ok_and(|url| matches!(url.scheme(), "http" | "https"))
+}
+
+/// Keys and request objects are only fetched over https, so that they cannot be swapped on the
+/// way.
+fn is_https_url(url: &str) -> bool {
+    Url::parse(url).is_ok_and(|url| url.scheme() == "https" && url.has_host())
+}
+
+/// After authorization or logout, browsers may only be sent to https urls, to http urls of the
+/// loopback interface, and to the reverse domain name schemes of native apps (RFC 8252 section 7).
+fn is_redirect_uri(uri: &str) -> bool {
+    let Ok(url) = Url::parse(uri) else {
+        return false;
+    };
+    if url.fragment().is_some() {
+        return false;
+    }
+
+    match url.scheme() {
+        "https" => url.has_host(),
+        "http" => match url.host() {
+            Some(Host::Domain(domain)) => domain == "localhost",
+            Some(Host::Ipv4(ip)) => ip.is_loopback(),
+            Some(Host::Ipv6(ip)) => ip.is_loopback(),
+            None => false,
+        },
+        scheme => scheme.contains('.'),
+    }
+}
+
+impl ClientMetadata {
+    /// Whether the client authenticates with a client secret, rather than with its keys.
+    pub fn uses_client_secret(&self) -> bool {
+        self.token_endpoint_auth_method != "private_key_jwt"
+    }
+
+    /// Scopes the client may request, all supported scopes when it registered none.
+    pub fn scopes(&self) -> Vec<&str> {
+        match &self.scope {
+            Some(scope) => scope::parse(scope),
+            None => Oauth2Service::SCOPES_SUPPORTED.to_vec(),
+        }
+    }
+}
+
+impl Validatable for ClientMetadata {
+    type Rejection = RegistrationError;
+
+    fn validate(&self) -> Result<(), Self::Rejection> {
+        if self.grant_types.is_empty() {
+            return Err(RegistrationError::InvalidClientMetadata(
+                "grant_types must not be empty",
+            ));
+        }
+        let supported = Oauth2Service::GRANT_TYPES_SUPPORTED;
+        if self
+            .grant_types
+            .iter()
+            .any(|grant_type| !supported.contains(&grant_type.as_str()))
+        {
+            return Err(RegistrationError::InvalidClientMetadata(
+                "unsupported grant type",
+            ));
+        }
+
+        if self.redirect_uris.is_empty()
+            && self
+                .grant_types
+                .iter()
+                .any(|grant| grant == "authorization_code")
+        {
+            return Err(RegistrationError::InvalidRedirectUri(
+                "redirect_uris is required for the authorization_code grant",
+            ));
+        }
+        if !self.redirect_uris.iter().all(|uri| is_redirect_uri(uri)) {
+            return Err(RegistrationError::InvalidRedirectUri(
+                "redirect uris must be https, loopback or private-use urls without fragment",
+            ));
+        }
+
+        if !self
+            .post_logout_redirect_uris
+            .iter()
+            .all(|uri| is_redirect_uri(uri))
+        {
+            return Err(RegistrationError::InvalidClientMetadata(
+                "post_logout_redirect_uris must be https, loopback or private-use urls",
+            ));
+        }
+
+        if self.backchannel_logout_uri.as_ref().is_some_and(|uri| {
+            uri.len() > 1024
+                || !is_web_url(uri)
+                || Url::parse(uri).is_ok_and(|url| url.fragment().is_some())
+        }) {
+            return Err(RegistrationError::InvalidClientMetadata(
+                "backchannel_logout_uri must be an http(s) url without fragment",
+            ));
+        }
+
+        if self.request_uris.iter().any(|uri| {
+            !is_https_url(uri) || Url::parse(uri).is_ok_and(|url| url.fragment().is_some())
+        }) {
+            return Err(RegistrationError::InvalidClientMetadata(
+                "request_uris must be https urls without fragment",
+            ));
+        }
+        if (!self.request_uris.is_empty() || self.require_signed_request_object)
+            && self.jwks.is_none()
+            && self.jwks_uri.is_none()
+        {
+            return Err(RegistrationError::InvalidClientMetadata(
+                "request objects require jwks or jwks_uri",
+            ));
+        }
+
+        if !Oauth2Service::TOKEN_ENDPOINT_AUTH_METHODS_SUPPORTED
+            .contains(&self.token_endpoint_auth_method.as_str())
+        {
+            return Err(RegistrationError::InvalidClientMetadata(
+                "unsupported token_endpoint_auth_method",
+            ));
+        }
+
+        if self.jwks.is_some() && self.jwks_uri.is_some() {
+            return Err(RegistrationError::InvalidClientMetadata(
+                "jwks and jwks_uri must not both be present",
+            ));
+        }
+        if self
+            .jwks
+            .as_ref()
+            .is_some_and(|jwks| serde_json::from_value::<JwkSet>(jwks.clone()).is_err())
+        {
+            return Err(RegistrationError::InvalidClientMetadata(
+                "jwks is malformed",
+            ));
+        }
+        if self
+            .jwks_uri
+            .as_ref()
+            .is_some_and(|uri| uri.len() > 1024 || !is_https_url(uri))
+        {
+            return Err(RegistrationError::InvalidClientMetadata(
+                "jwks_uri must be an https url",
+            ));
+        }
+        if !self.uses_client_secret() && self.jwks.is_none() && self.jwks_uri.is_none() {
+            return Err(RegistrationError::InvalidClientMetadata(
+                "private_key_jwt requires jwks or jwks_uri",
+            ));
+        }
+
+        if self
+            .client_name
+            .as_ref()
+            .is_some_and(|name| name.len() > 255)
+        {
+            return Err(RegistrationError::InvalidClientMetadata(
+                "client_name must be at most 255 characters",
+            ));
+        }
+
+        if self
+            .logo_uri
+            .as_ref()
+            .is_some_and(|uri| uri.len() > 1024 || !is_web_url(uri))
+        {
+            return Err(RegistrationError::InvalidClientMetadata(
+                "logo_uri must be an http(s) url",
+            ));
+        }
+
+        if self
+            .scopes()
+            .iter()
+            .any(|scope| !Oauth2Service::SCOPES_SUPPORTED.contains(scope))
+        {
+            return Err(RegistrationError::InvalidClientMetadata(
+                "unsupported scope",
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn metadata(metadata: serde_json::Value) -> ClientMetadata {
+        serde_json::from_value(metadata).unwrap()
+    }
+
+    fn with_redirect_uri(uri: &str) -> ClientMetadata {
+        metadata(json!({ "redirect_uris": [uri] }))
+    }
+
+    #[test]
+    fn redirect_uris_must_be_safe_to_send_browsers_to() {
+        for uri in [
+            "https://app.example/cb",
+            "http://localhost:8080/cb",
+            "http://127.0.0.1/cb",
+            "http://[::1]/cb",
+            "com.example.app:/cb",
+        ] {
+            assert!(with_redirect_uri(uri).validate().is_ok(), "{}", uri);
+        }
+
+        for uri in [
+            "javascript:alert(1)",
+            "data:text/html,hello",
+            "http://app.example/cb",
+            "https://app.example/cb#fragment",
+            "/cb",
+        ] {
+            assert!(
+                matches!(
+                    with_redirect_uri(uri).validate(),
+                    Err(RegistrationError::InvalidRedirectUri(_))
+                ),
+                "{}",
+                uri
+            );
+        }
+    }
+
+    #[test]
+    fn post_logout_redirect_uris_follow_the_redirect_uri_rule() {
+        let client = metadata(json!({
+            "redirect_uris": ["https://app.example/cb"],
+            "post_logout_redirect_uris": ["javascript:alert(1)"],
+        }));
+
+        assert!(matches!(
+            client.validate(),
+            Err(RegistrationError::InvalidClientMetadata(_))
+        ));
+    }
+
+    #[test]
+    fn authorization_code_grant_requires_redirect_uris() {
+        assert!(matches!(
+            metadata(json!({})).validate(),
+            Err(RegistrationError::InvalidRedirectUri(_))
+        ));
+        assert!(metadata(json!({ "grant_types": ["client_credentials"] }))
+            .validate()
+            .is_ok());
+    }
+
+    #[test]
+    fn private_key_jwt_requires_keys() {
+        let client = metadata(json!({
+            "grant_types": ["client_credentials"],
+            "token_endpoint_auth_method": "private_key_jwt",
+        }));
+
+        assert!(matches!(
+            client.validate(),
+            Err(RegistrationError::InvalidClientMetadata(_))
+        ));
+    }
+
+    #[test]
+    fn jwks_uri_must_be_https() {
+        let client = |jwks_uri| {
+            metadata(json!({
+                "grant_types": ["client_credentials"],
+                "token_endpoint_auth_method": "private_key_jwt",
+                "jwks_uri": jwks_uri,
+            }))
+        };
+
+        assert!(client("https://app.example/jwks.json").validate().is_ok());
+        assert!(matches!(
+            client("http://app.example/jwks.json").validate(),
+            Err(RegistrationError::InvalidClientMetadata(
+                "jwks_uri must be an https url"
+            ))
+        ));
+    }
+
+    #[test]
+    fn request_uris_must_be_https() {
+        let client = |request_uri| {
+            metadata(json!({
+                "redirect_uris": ["https://app.example/cb"],
+                "jwks_uri": "https://app.example/jwks.json",
+                "request_uris": [request_uri],
+            }))
+        };
+
+        assert!(client("https://app.example/request.jwt").validate().is_ok());
+        assert!(matches!(
+            client("http://app.example/request.jwt").validate(),
+            Err(RegistrationError::InvalidClientMetadata(
+                "request_uris must be https urls without fragment"
+            ))
+        ));
+    }
+
+    #[test]
+    fn unsupported_scopes_are_rejected() {
+        let client = metadata(json!({
+            "grant_types": ["client_credentials"],
+            "scope": "openid admin",
+        }));
+
+        assert!(matches!(
+            client.validate(),
+            Err(RegistrationError::InvalidClientMetadata(
+                "unsupported scope"
+            ))
+        ));
+    }
+}
@@ -0,0 +1,279 @@
+use crate::helpers::{random_token, InternalError};
+use crate::kvs::KvsPool;
+use argon2::password_hash::rand_core::{OsRng, RngCore};
+use chrono::{DateTime, Utc};
+use redis::{AsyncCommands, ExistenceCheck, SetExpiry, SetOptions};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use uuid::Uuid;
+
+/// Pending device authorizations (RFC 8628), kept in Redis until they are redeemed or expire.
+pub struct DeviceService {
+    kv_pool: Arc<KvsPool>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct DeviceAuthorization {
+    pub client_id: String,
+    pub scope: Option<String>,
+    pub user_code: String,
+    /// Minimum number of seconds between two polls of the token endpoint.
+    pub interval: u64,
+    pub status: DeviceStatus,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum DeviceStatus {
+    Pending,
+    Approved {
+        user_id: Uuid,
+        auth_time: DateTime<Utc>,
+    },
+    Denied,
+}
+
+pub enum DevicePoll {
+    Pending,
+    SlowDown,
+    Denied,
+    Expired,
+    /// The device code was issued to another client.
+    ClientMismatch,
+    Approved(DeviceAuthorization),
+}
+
+impl DeviceService {
+    pub const EXPIRES_IN: u64 = 60 * 10;
+    pub const INTERVAL: u64 = 5;
+
+    /// User codes avoid vowels and look-alike characters, so they are easy to type and cannot
+    /// spell words.
+    const USER_CODE_CHARSET: &'static [u8] = b"BCDFGHJKLMNPQRSTVWXZ";
+
+    pub fn new(kv_pool: Arc<KvsPool>) -> Self {
+        Self { kv_pool }
+    }
+}
+
+impl DeviceService {
+    /// Starts a device authorization, returning its device code.
+    pub async fn create(
+        &self,
+        client_id: String,
+        scope: Option<String>,
+    ) -> Result<(String, DeviceAuthorization), InternalError> {
+        let device_code = random_token();
+        let authorization = DeviceAuthorization {
+            client_id,
+            scope,
+            user_code: Self::generate_user_code(),
+            interval: Self::INTERVAL,
+            status: DeviceStatus::Pending,
+        };
+
+        let mut conn = self.kv_pool.get().await?;
+        let _: () = conn
+            .set_ex(
+                format!("user_code:{}", authorization.user_code),
+                &device_code,
+                Self::EXPIRES_IN,
+            )
+            .await?;
+        let _: () = conn
+            .set_ex(
+                format!("device_code:{}", device_code),
+                serde_json::to_string(&authorization)
+                    .expect("device authorization is serializable"),
+                Self::EXPIRES_IN,
+            )
+            .await?;
+
+        Ok((device_code, authorization))
+    }
+
+    pub async fn find_by_user_code(
+        &self,
+        user_code: &str,
+    ) -> Result<Option<(String, DeviceAuthorization)>, InternalError> {
+        let mut conn = self.kv_pool.get().await?;
+        let device_code: Option<String> = conn
+            .get(format!(
+                "user_code:{}",
+                Self::normalize_user_code(user_code)
+            ))
+            .await?;
+        let Some(device_code) = device_code else {
+            return Ok(None);
+        };
+
+        Ok(self
+            .find(&device_code)
+            .await?
+            .map(|authorization| (device_code, authorization)))
+    }
+
+    /// Records the user's decision on a pending authorization. Returns `false` when it no longer
+    /// exists or was already decided.
+    pub async fn decide(
+        &self,
+        device_code: &str,
+        status: DeviceStatus,
+    ) -> Result<bool, InternalError> {
+        if self.find(device_code).await?.is_none() {
+            return Ok(false);
+        }
+
+        // kept apart from the authorization, so that only the first decision is recorded and a
+        // poll slowing the device down cannot overwrite it
+        let mut conn = self.kv_pool.get().await?;
+        let decided: Option<String> = conn
+            .set_options(
+                format!("device_decision:{}", device_code),
+                serde_json::to_string(&status).expect("device status is serializable"),
+                SetOptions::default()
+                    .conditional_set(ExistenceCheck::NX)
+                    .with_expiration(SetExpiry::EX(Self::EXPIRES_IN)),
+            )
+            .await?;
+
+        Ok(decided.is_some())
+    }
+
+    /// Called for every token request of the device. An approved authorization is handed out
+    /// only once, and only to the client it was started by.
+    pub async fn poll(
+        &self,
+        device_code: &str,
+        client_id: &str,
+    ) -> Result<DevicePoll, InternalError> {
+        let Some(mut authorization) = self.find(device_code).await? else {
+            return Ok(DevicePoll::Expired);
+        };
+        if authorization.client_id != client_id {
+            return Ok(DevicePoll::ClientMismatch);
+        }
+
+        let mut conn = self.kv_pool.get().await?;
+        let polled: Option<String> = conn
+            .set_options(
+                format!("device_poll:{}", device_code),
+                1,
+                SetOptions::default()
+                    .conditional_set(ExistenceCheck::NX)
+                    .with_expiration(SetExpiry::EX(authorization.interval)),
+            )
+            .await?;
+        if polled.is_none() {
+            authorization.interval += Self::INTERVAL;
+            self.update(device_code, &authorization).await?;
+            return Ok(DevicePoll::SlowDown);
+        }
+
+        match authorization.status {
+            DeviceStatus::Pending => Ok(DevicePoll::Pending),
+            DeviceStatus::Denied => {
+                self.delete(device_code, &authorization).await?;
+                Ok(DevicePoll::Denied)
+            }
+            DeviceStatus::Approved { .. } => {
+                // whoever deletes the entry redeems it
+                let deleted: usize = conn.del(format!("device_code:{}", device_code)).await?;
+                if deleted == 0 {
+                    return Ok(DevicePoll::Expired);
+                }
+                let _: () = conn
+                    .del(&[
+                        format!("user_code:{}", authorization.user_code),
+                        format!("device_decision:{}", device_code),
+                    ])
+                    .await?;
+
+                Ok(DevicePoll::Approved(authorization))
+            }
+        }
+    }
+
+    async fn find(&self, device_code: &str) -> Result<Option<DeviceAuthorization>, InternalError> {
+        let mut conn = self.kv_pool.get().await?;
+        let authorization: Option<String> =
+            conn.get(format!("device_code:{}", device_code)).await?;
+        let Some(mut authorization) = authorization.and_then(|authorization| {
+            serde_json::from_str::<DeviceAuthorization>(&authorization).ok()
+        }) else {
+            return Ok(None);
+        };
+
+        let decision: Option<String> = conn.get(format!("device_decision:{}", device_code)).await?;
+        if let Some(status) = decision.and_then(|status| serde_json::from_str(&status).ok()) {
+            authorization.status = status;
+        }
+
+        Ok(Some(authorization))
+    }
+
+    async fn update(
+        &self,
+        device_code: &str,
+        authorization: &DeviceAuthorization,
+    ) -> Result<(), InternalError> {
+        let mut conn = self.kv_pool.get().await?;
+        let _: Option<String> = conn
+            .set_options(
+                format!("device_code:{}", device_code),
+                serde_json::to_string(authorization).expect("device authorization is serializable"),
+                SetOptions::default()
+                    .conditional_set(ExistenceCheck::XX)
+                    .with_expiration(SetExpiry::KEEPTTL),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn delete(
+        &self,
+        device_code: &str,
+        authorization: &DeviceAuthorization,
+    ) -> Result<(), InternalError> {
+        let mut conn = self.kv_pool.get().await?;
+        let _: () = conn
+            .del(&[
+                format!("device_code:{}", device_code),
+                format!("user_code:{}", authorization.user_code),
+                format!("device_decision:{}", device_code),
+            ])
+            .await?;
+
+        Ok(())
+    }
+
+    fn generate_user_code() -> String {
+        let mut bytes = [0u8; 8];
+        OsRng.fill_bytes(&mut bytes);
+
+        bytes
+            .iter()
+            .map(|byte| {
+                Self::USER_CODE_CHARSET[*byte as usize % Self::USER_CODE_CHARSET.len()] as char
+            })
+            .collect()
+    }
+
+    /// Accepts user codes typed in lower case or with separators, e.g. `bcdf-ghjk`.
+    fn normalize_user_code(user_code: &str) -> String {
+        user_code
+            .chars()
+            .filter(char::is_ascii_alphanumeric)
+            .map(|c| c.to_ascii_uppercase())
+            .collect()
+    }
+
+    /// Formats a user code for display as two groups of four characters.
+    pub fn display_user_code(user_code: &str) -> String {
+        match user_code.split_at_checked(4) {
+            Some((first, second)) => format!("{}-{}", first, second),
+            None => user_code.to_string(),
+        }
+    }
+}
@@ -0,0 +1,88 @@
+use crate::services::oauth2::Oauth2Service;
+use crate::services::tokens::TokenService;
+use jsonwebtoken::Algorithm;
+use serde::Serialize;
+use std::sync::Arc;
+
+pub struct DiscoveryService {
+    token_service: Arc<TokenService>,
+}
+
+/// OpenID Provider Metadata, as described in OpenID Connect Discovery 1.0 section 3.
+#[derive(Serialize)]
+pub struct ProviderMetadata {
+    issuer: String,
+    authorization_endpoint: String,
+    token_endpoint: String,
+    introspection_endpoint: String,
+    revocation_endpoint: String,
+    device_authorization_endpoint: String,
+    pushed_authorization_request_endpoint: String,
+    userinfo_endpoint: String,
+    registration_endpoint: String,
+    end_session_endpoint: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    jwks_uri: Option<String>,
+    #[serde(skip_serializing_if = "<[_]>::is_empty")]
+    scopes_supported: &'static [&'static str],
+    response_types_supported: &'static [&'static str],
+    grant_types_supported: &'static [&'static str],
+    subject_types_supported: &'static [&'static str],
+    id_token_signing_alg_values_supported: Vec<Algorithm>,
+    token_endpoint_auth_methods_supported: &'static [&'static str],
+    token_endpoint_auth_signing_alg_values_supported: &'static [Algorithm],
+    code_challenge_methods_supported: &'static [&'static str],
+    claims_supported: &'static [&'static str],
+    backchannel_logout_supported: bool,
+    backchannel_logout_session_supported: bool,
+    require_pushed_authorization_requests: bool,
+    request_parameter_supported: bool,
+    request_uri_parameter_supported: bool,
+    require_request_uri_registration: bool,
+    request_object_signing_alg_values_supported: &'static [Algorithm],
+}
+
+impl DiscoveryService {
+    pub fn new(token_service: Arc<TokenService>) -> Self {
+        Self { token_service }
+    }
+}
+
+impl DiscoveryService {
+    pub fn provider_metadata(&self) -> ProviderMetadata {
+        let issuer = self.token_service.issuer();
+
+        ProviderMetadata {
+            issuer: issuer.to_string(),
+            authorization_endpoint: format!("{}/oauth2/login", issuer),
+            token_endpoint: format!("{}/oauth2/token", issuer),
+            introspection_endpoint: format!("{}/oauth2/introspect", issuer),
+            revocation_endpoint: format!("{}/oauth2/revoke", issuer),
+            device_authorization_endpoint: format!("{}/oauth2/device_authorization", issuer),
+            pushed_authorization_request_endpoint: format!("{}/oauth2/par", issuer),
+            userinfo_endpoint: format!("{}/oauth2/userinfo", issuer),
+            registration_endpoint: format!("{}/oauth2/register", issuer),
+            end_session_endpoint: format!("{}/oauth2/logout", issuer),
+            jwks_uri: (!self.token_service.jwks().keys.is_empty())
+                .then(|| format!("{}/.well-known/jwks.json", issuer)),
+            scopes_supported: Oauth2Service::SCOPES_SUPPORTED,
+            response_types_supported: Oauth2Service::RESPONSE_TYPES_SUPPORTED,
+            grant_types_supported: Oauth2Service::GRANT_TYPES_SUPPORTED,
+            subject_types_supported: &["public"],
+            id_token_signing_alg_values_supported: self.token_service.signing_algorithms(),
+            token_endpoint_auth_methods_supported:
+                Oauth2Service::TOKEN_ENDPOINT_AUTH_METHODS_SUPPORTED,
+            token_endpoint_auth_signing_alg_values_supported:
+                TokenService::CLIENT_ASSERTION_ALGORITHMS,
+            code_challenge_methods_supported: Oauth2Service::CODE_CHALLENGE_METHODS_SUPPORTED,
+            claims_supported: Oauth2Service::CLAIMS_SUPPORTED,
+            backchannel_logout_supported: true,
+            backchannel_logout_session_supported: true,
+            require_pushed_authorization_requests: false,
+            request_parameter_supported: true,
+            request_uri_parameter_supported: true,
+            require_request_uri_registration: true,
+            request_object_signing_alg_values_supported: TokenService::CLIENT_ASSERTION_ALGORITHMS,
+        }
+    }
+}
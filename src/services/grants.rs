@@ -0,0 +1,235 @@
+use crate::db::DbPool;
+use crate::helpers::{random_token, InternalError};
+use crate::kvs::KvsPool;
+use crate::services::clients::Client;
+use crate::services::oauth2::{scope, AuthorizationRequest};
+use crate::services::sessions::Session;
+use redis::AsyncCommands;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use uuid::Uuid;
+
+pub use models::GrantWithClient;
+
+/// Remembers which scopes each user approved for each client, and holds authorization requests
+/// that are waiting for the user's consent.
+pub struct GrantService {
+    db_pool: Arc<DbPool>,
+    kv_pool: Arc<KvsPool>,
+}
+
+/// An authenticated authorization request waiting on the consent page.
+#[derive(Serialize, Deserialize)]
+pub struct ConsentRequest {
+    pub session: Session,
+    pub request: AuthorizationRequest,
+}
+
+impl GrantService {
+    pub fn new(db_pool: Arc<DbPool>, kv_pool: Arc<KvsPool>) -> Self {
+        Self { db_pool, kv_pool }
+    }
+}
+
+impl GrantService {
+    /// Whether `user_id` already approved every scope of `scope` for `client`.
+    pub async fn is_granted(
+        &self,
+        user_id: Uuid,
+        client: &Client,
+        scope: Option<&str>,
+    ) -> Result<bool, InternalError> {
+        let mut conn = self.db_pool.get().await?;
+        let Some(grant) = models::Grant::find(user_id, client.id, &mut conn).await? else {
+            return Ok(false);
+        };
+
+        let granted = scope::parse(&grant.scope);
+        Ok(scope
+            .map(scope::parse)
+            .unwrap_or_default()
+            .iter()
+            .all(|scope| granted.contains(scope)))
+    }
+
+    /// Adds `scope` to the scopes `user_id` approved for `client`.
+    pub async fn grant(
+        &self,
+        user_id: Uuid,
+        client: &Client,
+        scope: Option<&str>,
+    ) -> Result<(), InternalError> {
+        let mut conn = self.db_pool.get().await?;
+        let existing = models::Grant::find(user_id, client.id, &mut conn).await?;
+
+        let mut granted = existing
+            .as_ref()
+            .map(|grant| scope::parse(&grant.scope))
+            .unwrap_or_default();
+        for scope in scope.map(scope::parse).unwrap_or_default() {
+            if !granted.contains(&scope) {
+                granted.push(scope);
+            }
+        }
+
+        models::Grant::upsert(
+            user_id,
+            client.id,
+            scope::join(&granted).unwrap_or_default(),
+            &mut conn,
+        )
+        .await
+        .map_err(Into::into)
+    }
+
+    pub async fn list(&self, user_id: Uuid) -> Result<Vec<GrantWithClient>, InternalError> {
+        let mut conn = self.db_pool.get().await?;
+        GrantWithClient::find_by_user(user_id, &mut conn)
+            .await
+            .map_err(Into::into)
+    }
+
+    /// Returns whether there was a grant to revoke.
+    pub async fn revoke(&self, user_id: Uuid, client: &Client) -> Result<bool, InternalError> {
+        let mut conn = self.db_pool.get().await?;
+        let deleted = models::Grant::delete(user_id, client.id, &mut conn).await?;
+
+        Ok(deleted > 0)
+    }
+
+    /// Parks `request` until the user decides, returning the challenge identifying it.
+    pub async fn save_consent_request(
+        &self,
+        request: &ConsentRequest,
+    ) -> Result<String, InternalError> {
+        let challenge = random_token();
+
+        let mut conn = self.kv_pool.get().await?;
+        let _: () = conn
+            .set_ex(
+                format!("consent_request:{}", challenge),
+                serde_json::to_string(request).expect("consent request is serializable"),
+                60 * 10,
+            )
+            .await?;
+
+        Ok(challenge)
+    }
+
+    pub async fn get_consent_request(
+        &self,
+        challenge: &str,
+    ) -> Result<Option<ConsentRequest>, InternalError> {
+        let mut conn = self.kv_pool.get().await?;
+        let request: Option<String> = conn.get(format!("consent_request:{}", challenge)).await?;
+
+        Ok(request.and_then(|request| serde_json::from_str(&request).ok()))
+    }
+
+    /// Like `get_consent_request`, but the request can only be taken once.
+    pub async fn take_consent_request(
+        &self,
+        challenge: &str,
+    ) -> Result<Option<ConsentRequest>, InternalError> {
+        let mut conn = self.kv_pool.get().await?;
+        let request: Option<String> = conn
+            .get_del(format!("consent_request:{}", challenge))
+            .await?;
+
+        Ok(request.and_then(|request| serde_json::from_str(&request).ok()))
+    }
+}
+
+mod models {
+    use crate::db::schema::{clients, grants};
+    use chrono::{DateTime, Utc};
+    use diesel::upsert::excluded;
+    use diesel::{
+        ExpressionMethods, OptionalExtension, QueryDsl, Queryable, Selectable, SelectableHelper,
+    };
+    use diesel_async::{AsyncPgConnection, RunQueryDsl};
+    use serde::Serialize;
+    use uuid::Uuid;
+
+    #[derive(Debug, Selectable, Queryable)]
+    pub struct Grant {
+        pub scope: String,
+    }
+
+    impl Grant {
+        pub async fn find(
+            user_id: Uuid,
+            client_id: Uuid,
+            conn: &mut AsyncPgConnection,
+        ) -> Result<Option<Self>, diesel::result::Error> {
+            grants::table
+                .select(Self::as_select())
+                .filter(grants::user_id.eq(user_id))
+                .filter(grants::client_id.eq(client_id))
+                .first(conn)
+                .await
+                .optional()
+        }
+
+        pub async fn upsert(
+            user_id: Uuid,
+            client_id: Uuid,
+            scope: String,
+            conn: &mut AsyncPgConnection,
+        ) -> Result<(), diesel::result::Error> {
+            diesel::insert_into(grants::table)
+                .values((
+                    grants::user_id.eq(user_id),
+                    grants::client_id.eq(client_id),
+                    grants::scope.eq(scope),
+                ))
+                .on_conflict((grants::user_id, grants::client_id))
+                .do_update()
+                .set(grants::scope.eq(excluded(grants::scope)))
+                .execute(conn)
+                .await?;
+
+            Ok(())
+        }
+
+        pub async fn delete(
+            user_id: Uuid,
+            client_id: Uuid,
+            conn: &mut AsyncPgConnection,
+        ) -> Result<usize, diesel::result::Error> {
+            diesel::delete(grants::table)
+                .filter(grants::user_id.eq(user_id))
+                .filter(grants::client_id.eq(client_id))
+                .execute(conn)
+                .await
+        }
+    }
+
+    #[derive(Debug, Queryable, Serialize)]
+    pub struct GrantWithClient {
+        pub client_id: String,
+        pub client_name: Option<String>,
+        pub scope: String,
+        pub granted_at: DateTime<Utc>,
+    }
+
+    impl GrantWithClient {
+        pub async fn find_by_user(
+            user_id: Uuid,
+            conn: &mut AsyncPgConnection,
+        ) -> Result<Vec<Self>, diesel::result::Error> {
+            grants::table
+                .inner_join(clients::table)
+                .select((
+                    clients::client_id,
+                    clients::name,
+                    grants::scope,
+                    grants::created_at,
+                ))
+                .filter(grants::user_id.eq(user_id))
+                .order(grants::created_at.asc())
+                .load(conn)
+                .await
+        }
+    }
+}
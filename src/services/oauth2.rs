@@ -1,8 +1,22 @@
+pub mod client_auth;
+pub mod pkce;
+pub mod scope;
+
 use crate::helpers::InternalError;
 use crate::services::clients::{Client, ClientService};
-use crate::services::tokens::jwt::{Claims, JwtType, JwtVerifyError};
+use crate::services::devices::{DevicePoll, DeviceService, DeviceStatus};
+use crate::services::oauth2::client_auth::{ClientAuthParams, ClientCredentials};
+use crate::services::oauth2::pkce::{CodeChallenge, CodeChallengeMethod};
+use crate::services::refresh_tokens::{
+    NewRefreshTokenFamily, RefreshTokenFamily, RefreshTokenService, Rotation,
+};
+use crate::services::resource_servers::ResourceServerService;
+use crate::services::sessions::Session;
+use crate::services::token_exchange_policies::TokenExchangePolicyService;
+use crate::services::tokens::jwt::{Actor, Claims, JwtType, JwtVerifyError};
 use crate::services::tokens::TokenService;
-use axum::http::StatusCode;
+use crate::services::users::UserService;
+use axum::http::{header, StatusCode};
 use axum::response::{IntoResponse, Response};
 use axum::Json;
 use serde::{Deserialize, Serialize};
@@ -12,15 +26,117 @@ use uuid::Uuid;
 pub struct Oauth2Service {
     pub token_service: Arc<TokenService>,
     pub client_service: Arc<ClientService>,
+    pub user_service: Arc<UserService>,
+    pub refresh_token_service: Arc<RefreshTokenService>,
+    pub device_service: Arc<DeviceService>,
+    pub resource_server_service: Arc<ResourceServerService>,
+    pub token_exchange_policy_service: Arc<TokenExchangePolicyService>,
+}
+
+/// A validated authorization request, ready to be turned into an authorization code once the
+/// user is authenticated and has consented.
+#[derive(Serialize, Deserialize)]
+pub struct AuthorizationRequest {
+    pub client_id: String,
+    pub redirect_uri: String,
+    /// Whether the client sent the redirect uri, rather than relying on its only registered one.
+    #[serde(default)]
+    pub redirect_uri_sent: bool,
+    pub scope: Option<String>,
+    pub nonce: Option<String>,
+    pub code_challenge: Option<CodeChallenge>,
+    pub state: Option<String>,
+    /// Resource indicator (RFC 8707) of the resource server the client wants access to.
+    #[serde(default)]
+    pub resource: Option<String>,
 }
 
 #[derive(Deserialize)]
 pub struct TokenParams {
     grant_type: String,
-    code: String,
-    redirect_uri: String,
-    client_id: String,
-    client_secret: String,
+    code: Option<String>,
+    redirect_uri: Option<String>,
+    refresh_token: Option<String>,
+    device_code: Option<String>,
+    code_verifier: Option<String>,
+    scope: Option<String>,
+    resource: Option<String>,
+    audience: Option<String>,
+    subject_token: Option<String>,
+    subject_token_type: Option<String>,
+    actor_token: Option<String>,
+    actor_token_type: Option<String>,
+    requested_token_type: Option<String>,
+    #[serde(flatten)]
+    pub client_auth: ClientAuthParams,
+}
+
+#[derive(Deserialize)]
+pub struct DeviceAuthorizationParams {
+    scope: Option<String>,
+    #[serde(flatten)]
+    pub client_auth: ClientAuthParams,
+}
+
+/// Device authorization response (RFC 8628 section 3.2).
+#[derive(Serialize)]
+pub struct DeviceAuthorizationResponse {
+    device_code: String,
+    user_code: String,
+    verification_uri: String,
+    verification_uri_complete: String,
+    expires_in: u64,
+    interval: u64,
+}
+
+#[derive(Deserialize)]
+pub struct IntrospectionParams {
+    token: String,
+    #[serde(flatten)]
+    pub client_auth: ClientAuthParams,
+}
+
+/// Token introspection response (RFC 7662). Inactive tokens only carry `active`.
+#[derive(Default, Serialize)]
+pub struct Introspection {
+    active: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    scope: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    client_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    aud: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    sub: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    exp: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    iat: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    iss: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    token_type: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    act: Option<Actor>,
+}
+
+#[derive(Deserialize)]
+pub struct RevocationParams {
+    token: String,
+    #[serde(flatten)]
+    pub client_auth: ClientAuthParams,
+}
+
+/// OpenID Connect UserInfo response; claims are included according to the granted scopes.
+#[derive(Serialize)]
+pub struct UserInfo {
+    sub: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    preferred_username: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    email: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    email_verified: Option<bool>,
 }
 
 #[derive(Serialize)]
@@ -28,48 +144,273 @@ pub struct AccessToken {
     access_token: String,
     token_type: &'static str,
     expires_in: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
     refresh_token: Option<String>,
     scope: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    id_token: Option<String>,
+    /// Type of a token issued by token exchange.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    issued_token_type: Option<&'static str>,
 }
 
 impl Oauth2Service {
-    pub fn new(token_service: Arc<TokenService>, client_service: Arc<ClientService>) -> Self {
+    pub const GRANT_TYPES_SUPPORTED: &'static [&'static str] = &[
+        "authorization_code",
+        "refresh_token",
+        "client_credentials",
+        Self::DEVICE_CODE_GRANT_TYPE,
+        Self::TOKEN_EXCHANGE_GRANT_TYPE,
+    ];
+    pub const DEVICE_CODE_GRANT_TYPE: &'static str = "urn:ietf:params:oauth:grant-type:device_code";
+    pub const TOKEN_EXCHANGE_GRANT_TYPE: &'static str =
+        "urn:ietf:params:oauth:grant-type:token-exchange";
+    /// The only token type that can be exchanged, and issued by token exchange.
+    pub const ACCESS_TOKEN_TYPE: &'static str = "urn:ietf:params:oauth:token-type:access_token";
+    pub const RESPONSE_TYPES_SUPPORTED: &'static [&'static str] = &["code"];
+    pub const TOKEN_ENDPOINT_AUTH_METHODS_SUPPORTED: &'static [&'static str] = &[
+        "client_secret_basic",
+        "client_secret_post",
+        "private_key_jwt",
+    ];
+    pub const CODE_CHALLENGE_METHODS_SUPPORTED: &'static [&'static str] = &["S256", "plain"];
+    pub const SCOPES_SUPPORTED: &'static [&'static str] = &["openid", "profile", "email"];
+    pub const CLAIMS_SUPPORTED: &'static [&'static str] = &[
+        "sub",
+        "iss",
+        "aud",
+        "exp",
+        "iat",
+        "auth_time",
+        "nonce",
+        "email",
+        "email_verified",
+        "preferred_username",
+        "sid",
+    ];
+
+    fn access_token_expiry() -> chrono::Duration {
+        chrono::Duration::minutes(60)
+    }
+
+    pub fn new(
+        token_service: Arc<TokenService>,
+        client_service: Arc<ClientService>,
+        user_service: Arc<UserService>,
+        refresh_token_service: Arc<RefreshTokenService>,
+        device_service: Arc<DeviceService>,
+        resource_server_service: Arc<ResourceServerService>,
+        token_exchange_policy_service: Arc<TokenExchangePolicyService>,
+    ) -> Self {
         Self {
             token_service,
             client_service,
+            user_service,
+            refresh_token_service,
+            device_service,
+            resource_server_service,
+            token_exchange_policy_service,
         }
     }
 
     pub fn create_authorization_code(
         &self,
-        client_id: String,
-        user_id: Uuid,
+        session: &Session,
+        request: AuthorizationRequest,
     ) -> Result<String, InternalError> {
         let expiry = chrono::Duration::minutes(5);
         self.token_service
-            .create_authorization_code(client_id, user_id, expiry)
+            .create_authorization_code(session, request, expiry)
     }
 
-    pub async fn access_token(
+    /// Revokes every refresh token `user_id` holds for `client_id`, along with the access tokens
+    /// derived from them.
+    pub async fn revoke_user_tokens(
         &self,
-        token_params: &TokenParams,
-    ) -> Result<AccessToken, AccessTokenError> {
+        user_id: Uuid,
+        client_id: &str,
+    ) -> Result<(), InternalError> {
+        let family_ids = self
+            .refresh_token_service
+            .revoke_all(user_id, client_id)
+            .await?;
+        self.revoke_access_tokens(family_ids).await
+    }
+
+    /// Revokes the access tokens derived from the refresh token families `family_ids`.
+    pub async fn revoke_access_tokens(&self, family_ids: Vec<Uuid>) -> Result<(), InternalError> {
+        for family_id in family_ids {
+            self.token_service
+                .revoke_family(family_id, Self::access_token_expiry())
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    /// Picks the resource an access token is issued for. Within a grant limited to a resource,
+    /// only that resource can be requested; otherwise any registered resource server can.
+    pub async fn resolve_resource(
+        &self,
+        requested: Option<&str>,
+        granted: Option<&str>,
+    ) -> Result<Option<String>, AccessTokenError> {
+        let Some(requested) = requested else {
+            return Ok(granted.map(ToString::to_string));
+        };
+
+        if granted.is_some_and(|granted| granted != requested) {
+            return Err(AccessTokenError::InvalidTarget("resource was not granted"));
+        }
+        if !ResourceServerService::is_valid_identifier(requested) {
+            return Err(AccessTokenError::InvalidTarget(
+                "resource must be an absolute uri without fragment",
+            ));
+        }
+        if !self
+            .resource_server_service
+            .is_registered(requested)
+            .await?
+        {
+            tracing::info!(resource = requested, "unknown resource server");
+            return Err(AccessTokenError::InvalidTarget("unknown resource"));
+        }
+
+        Ok(Some(requested.to_string()))
+    }
+
+    /// Narrows a granted scope down to the requested one, which must be a subset of it.
+    fn narrow_scope(
+        requested: Option<&str>,
+        granted: Option<&str>,
+    ) -> Result<Option<String>, AccessTokenError> {
+        let Some(requested) = requested else {
+            return Ok(granted.map(ToString::to_string));
+        };
+
+        let requested = scope::parse(requested);
+        let granted = granted.map(scope::parse).unwrap_or_default();
+        if let Some(scope) = requested.iter().find(|scope| !granted.contains(scope)) {
+            return Err(AccessTokenError::InvalidScope(scope.to_string()));
+        }
+
+        Ok(scope::join(&requested))
+    }
+
+    /// The part of a previously granted `scope` that `client` may still request.
+    async fn allowed_scope(
+        &self,
+        client: &Client,
+        scope: Option<String>,
+    ) -> Result<Option<String>, InternalError> {
+        let Some(scope) = scope else {
+            return Ok(None);
+        };
+        let allowed = self.client_service.allowed_scopes(client).await?;
+
+        let scopes: Vec<&str> = scope::parse(&scope)
+            .into_iter()
+            .filter(|scope| allowed.iter().any(|allowed| allowed == scope))
+            .collect();
+        Ok(scope::join(&scopes))
+    }
+
+    /// Checks the requested scope against the scopes `client` may request, returning the scope
+    /// to grant.
+    pub async fn grant_scope(
+        &self,
+        client: &Client,
+        scope: Option<&str>,
+    ) -> Result<Option<String>, ScopeError> {
+        let requested = scope.map(scope::parse).unwrap_or_default();
+        let allowed = self.client_service.allowed_scopes(client).await?;
+
+        if let Some(scope) = requested
+            .iter()
+            .find(|scope| !allowed.iter().any(|allowed| allowed == *scope))
+        {
+            return Err(ScopeError::NotAllowed(scope.to_string()));
+        }
+
+        Ok(scope::join(&requested))
+    }
+
+    /// Authenticates a client with its credentials, which must use the client's registered
+    /// authentication method.
+    pub async fn authenticate_client(
+        &self,
+        credentials: &ClientCredentials,
+    ) -> Result<Client, AccessTokenError> {
         let client = self
             .client_service
-            .get_by_client_id(&token_params.client_id)
+            .get_by_client_id(credentials.client_id())
             .await?
-            .ok_or(AccessTokenError::ClientAuthenticationFailed)?;
+            .ok_or_else(|| credentials.authentication_failed())?;
 
-        if !client.is_secret_match(&token_params.client_secret)? {
-            tracing::warn!("mismatch client secret");
-            return Err(AccessTokenError::ClientAuthenticationFailed);
+        if client.token_endpoint_auth_method != credentials.method() {
+            tracing::warn!(
+                client_id = client.client_id,
+                method = credentials.method(),
+                registered_method = client.token_endpoint_auth_method,
+                "client used an unregistered authentication method"
+            );
+            return Err(credentials.authentication_failed());
         }
 
-        let claims = self.token_service.verify_any(&token_params.code)?;
-        match token_params.grant_type.as_str() {
-            "authorization_code" => {
-                self.authorization_code_flow(&claims, &client, token_params)
+        match credentials {
+            ClientCredentials::Basic { client_secret, .. }
+            | ClientCredentials::Post { client_secret, .. } => {
+                if !client.is_secret_match(client_secret)? {
+                    tracing::warn!("mismatch client secret");
+                    return Err(credentials.authentication_failed());
+                }
+            }
+            ClientCredentials::PrivateKeyJwt {
+                client_assertion, ..
+            } => {
+                let jwks = self
+                    .client_service
+                    .jwks(&client)
+                    .await
+                    .ok_or(AccessTokenError::ClientAuthenticationFailed)?;
+                match self
+                    .token_service
+                    .verify_client_assertion(client_assertion, &client.client_id, &jwks)
                     .await
+                {
+                    Ok(()) => {}
+                    Err(JwtVerifyError::InternalError(e)) => return Err(e.into()),
+                    Err(_) => {
+                        tracing::warn!(client.client_id, "invalid client assertion");
+                        return Err(AccessTokenError::ClientAuthenticationFailed);
+                    }
+                }
+            }
+        }
+
+        Ok(client)
+    }
+
+    pub async fn access_token(
+        &self,
+        credentials: &ClientCredentials,
+        token_params: &TokenParams,
+    ) -> Result<AccessToken, AccessTokenError> {
+        let client = self.authenticate_client(credentials).await?;
+
+        if Self::GRANT_TYPES_SUPPORTED.contains(&token_params.grant_type.as_str())
+            && !client.allows_grant_type(&token_params.grant_type)
+        {
+            return Err(AccessTokenError::UnauthorizedClient);
+        }
+
+        match token_params.grant_type.as_str() {
+            "authorization_code" => self.authorization_code_flow(&client, token_params).await,
+            "refresh_token" => self.refresh_token_flow(&client, token_params).await,
+            "client_credentials" => self.client_credentials_flow(&client, token_params).await,
+            Self::DEVICE_CODE_GRANT_TYPE => self.device_code_flow(&client, token_params).await,
+            Self::TOKEN_EXCHANGE_GRANT_TYPE => {
+                self.token_exchange_flow(&client, token_params).await
             }
             _ => Err(AccessTokenError::UnsupportedGrantType),
         }
@@ -77,34 +418,545 @@ impl Oauth2Service {
 
     async fn authorization_code_flow(
         &self,
-        claims: &Claims,
         client: &Client,
         token_params: &TokenParams,
     ) -> Result<AccessToken, AccessTokenError> {
+        let code = token_params
+            .code
+            .as_deref()
+            .ok_or(AccessTokenError::MissingParameter("code"))?;
+        let claims = self.token_service.verify_any(code)?;
+
         if claims.jwt_type != JwtType::AuthorizationCode {
             return Err(AccessTokenError::TokenTypeMismatch);
         }
 
-        if claims.aud != token_params.client_id {
+        if claims.aud != client.client_id {
             return Err(AccessTokenError::TokenAudienceMismatch);
         }
+        let user_id = claims.user_id().ok_or(JwtVerifyError::InvalidToken)?;
 
-        if token_params.redirect_uri != client.redirect_uri {
+        // the redirect uri may only be left out when it was also defaulted at authorization
+        if claims.redirect_uri_sent && token_params.redirect_uri.is_none() {
+            return Err(AccessTokenError::MissingParameter("redirect_uri"));
+        }
+        let redirect_uri = client
+            .resolve_redirect_uri(token_params.redirect_uri.as_deref())
+            .ok_or(AccessTokenError::RedirectUriMismatch)?;
+        if claims
+            .redirect_uri
+            .as_deref()
+            .is_some_and(|expected| expected != redirect_uri)
+        {
             return Err(AccessTokenError::RedirectUriMismatch);
         }
 
+        let code_challenge = match (claims.code_challenge, claims.code_challenge_method) {
+            (Some(challenge), Some(method)) => Some(CodeChallenge {
+                challenge,
+                method: CodeChallengeMethod::parse(&method).ok_or(JwtVerifyError::InvalidToken)?,
+            }),
+            _ => None,
+        };
+        match (code_challenge, token_params.code_verifier.as_deref()) {
+            (Some(code_challenge), Some(code_verifier)) => {
+                if !code_challenge.verify(code_verifier) {
+                    return Err(AccessTokenError::CodeVerifierMismatch);
+                }
+            }
+            (Some(_), None) => return Err(AccessTokenError::MissingParameter("code_verifier")),
+            // a verifier without a challenge means the challenge was stripped from the request
+            (None, Some(_)) => return Err(AccessTokenError::CodeVerifierMismatch),
+            (None, None) if client.require_pkce => {
+                return Err(AccessTokenError::CodeChallengeRequired)
+            }
+            (None, None) => {}
+        }
+
+        let resource = self
+            .resolve_resource(token_params.resource.as_deref(), claims.resource.as_deref())
+            .await?;
+
         if !self
             .token_service
-            .mark_authorization_code_as_used(&token_params.code)
+            .mark_authorization_code_as_used(code)
             .await?
         {
             return Err(AccessTokenError::AuthorizationCodeUsed);
         };
 
-        let expiry = chrono::Duration::minutes(60);
+        let auth_time = claims.auth_time.unwrap_or(claims.iat) as i64;
+        let family = self
+            .refresh_token_service
+            .start_family(
+                NewRefreshTokenFamily::new(
+                    claims.aud,
+                    user_id,
+                    claims.scope,
+                    chrono::DateTime::from_timestamp(auth_time, 0).unwrap_or_else(chrono::Utc::now),
+                    chrono::Duration::days(30),
+                )
+                .set_sid(claims.sid)
+                .set_resource(claims.resource),
+            )
+            .await?;
+
+        self.issue_tokens(&family, family.scope.clone(), resource, claims.nonce)
+            .await
+    }
+
+    async fn refresh_token_flow(
+        &self,
+        client: &Client,
+        token_params: &TokenParams,
+    ) -> Result<AccessToken, AccessTokenError> {
+        let refresh_token = token_params
+            .refresh_token
+            .as_deref()
+            .ok_or(AccessTokenError::MissingParameter("refresh_token"))?;
+        let claims = self.token_service.verify_any(refresh_token)?;
+
+        if claims.jwt_type != JwtType::RefreshToken {
+            return Err(AccessTokenError::TokenTypeMismatch);
+        }
+
+        if claims.aud != client.client_id {
+            return Err(AccessTokenError::TokenAudienceMismatch);
+        }
+
+        let (Some(family_id), Some(jti)) = (claims.family_id, claims.jti) else {
+            return Err(JwtVerifyError::InvalidToken.into());
+        };
+
+        // the new tokens may be limited to a subset of the originally granted scope, and lose
+        // the scopes taken off the client's allow-list since
+        let scope = Self::narrow_scope(token_params.scope.as_deref(), claims.scope.as_deref())?;
+        let scope = self.allowed_scope(client, scope).await?;
+
+        let resource = self
+            .resolve_resource(token_params.resource.as_deref(), claims.resource.as_deref())
+            .await?;
+
+        let family = match self.refresh_token_service.rotate(family_id, jti).await? {
+            Rotation::Rotated(family) => family,
+            Rotation::Reused => {
+                self.token_service
+                    .revoke_family(family_id, Self::access_token_expiry())
+                    .await?;
+                return Err(AccessTokenError::RefreshTokenReused);
+            }
+            Rotation::Invalid => return Err(AccessTokenError::RefreshTokenRevoked),
+        };
+
+        self.issue_tokens(&family, scope, resource, None).await
+    }
+
+    /// Starts the device authorization grant for a client that cannot receive redirects.
+    pub async fn device_authorization(
+        &self,
+        credentials: &ClientCredentials,
+        params: &DeviceAuthorizationParams,
+    ) -> Result<DeviceAuthorizationResponse, AccessTokenError> {
+        let client = self.authenticate_client(credentials).await?;
+
+        if !client.allows_grant_type(Self::DEVICE_CODE_GRANT_TYPE) {
+            return Err(AccessTokenError::UnauthorizedClient);
+        }
+
+        let scope = match self.grant_scope(&client, params.scope.as_deref()).await {
+            Ok(scope) => scope,
+            Err(ScopeError::NotAllowed(scope)) => {
+                return Err(AccessTokenError::InvalidScope(scope))
+            }
+            Err(ScopeError::InternalError(e)) => return Err(e.into()),
+        };
+
+        let (device_code, authorization) = self
+            .device_service
+            .create(client.client_id.clone(), scope)
+            .await?;
+
+        let user_code = DeviceService::display_user_code(&authorization.user_code);
+        let verification_uri = format!("{}/device", self.token_service.issuer());
+        let verification_uri_complete = format!(
+            "{}?{}",
+            verification_uri,
+            url::form_urlencoded::Serializer::new(String::new())
+                .append_pair("user_code", &user_code)
+                .finish()
+        );
+
+        Ok(DeviceAuthorizationResponse {
+            device_code,
+            user_code,
+            verification_uri,
+            verification_uri_complete,
+            expires_in: DeviceService::EXPIRES_IN,
+            interval: authorization.interval,
+        })
+    }
+
+    async fn device_code_flow(
+        &self,
+        client: &Client,
+        token_params: &TokenParams,
+    ) -> Result<AccessToken, AccessTokenError> {
+        let device_code = token_params
+            .device_code
+            .as_deref()
+            .ok_or(AccessTokenError::MissingParameter("device_code"))?;
+
+        let authorization = match self
+            .device_service
+            .poll(device_code, &client.client_id)
+            .await?
+        {
+            DevicePoll::Approved(authorization) => authorization,
+            DevicePoll::Pending => return Err(AccessTokenError::AuthorizationPending),
+            DevicePoll::SlowDown => return Err(AccessTokenError::SlowDown),
+            DevicePoll::Denied => return Err(AccessTokenError::AccessDenied),
+            DevicePoll::Expired => return Err(AccessTokenError::ExpiredToken),
+            DevicePoll::ClientMismatch => return Err(AccessTokenError::TokenAudienceMismatch),
+        };
+
+        let DeviceStatus::Approved { user_id, auth_time } = authorization.status else {
+            return Err(AccessTokenError::AccessDenied);
+        };
+
+        let resource = self
+            .resolve_resource(token_params.resource.as_deref(), None)
+            .await?;
+
+        let family = self
+            .refresh_token_service
+            .start_family(NewRefreshTokenFamily::new(
+                authorization.client_id,
+                user_id,
+                authorization.scope,
+                auth_time,
+                chrono::Duration::days(30),
+            ))
+            .await?;
+
+        self.issue_tokens(&family, family.scope.clone(), resource, None)
+            .await
+    }
+
+    /// Describes an access or refresh token to an authenticated client. Refresh tokens are only
+    /// active while they are the latest token of a live family.
+    pub async fn introspect(
+        &self,
+        credentials: &ClientCredentials,
+        params: &IntrospectionParams,
+    ) -> Result<Introspection, AccessTokenError> {
+        self.authenticate_client(credentials).await?;
+
+        let claims = match self.token_service.verify_any(&params.token) {
+            Ok(claims) => claims,
+            Err(JwtVerifyError::InternalError(e)) => return Err(e.into()),
+            Err(_) => return Ok(Introspection::default()),
+        };
+
+        let token_type = match claims.jwt_type {
+            JwtType::AccessToken => {
+                if self.token_service.is_revoked(&claims).await? {
+                    return Ok(Introspection::default());
+                }
+
+                "Bearer"
+            }
+            JwtType::RefreshToken => {
+                let (Some(family_id), Some(jti)) = (claims.family_id, claims.jti) else {
+                    return Ok(Introspection::default());
+                };
+                if !self
+                    .refresh_token_service
+                    .is_current(family_id, jti)
+                    .await?
+                {
+                    return Ok(Introspection::default());
+                }
+
+                "refresh_token"
+            }
+            _ => return Ok(Introspection::default()),
+        };
+
+        Ok(Introspection {
+            active: true,
+            client_id: Some(claims.client_id().to_string()),
+            scope: claims.scope,
+            aud: Some(claims.aud),
+            sub: Some(claims.sub),
+            exp: Some(claims.exp),
+            iat: Some(claims.iat),
+            iss: Some(claims.iss),
+            token_type: Some(token_type),
+            act: claims.act,
+        })
+    }
+
+    /// Revokes an access token, or a refresh token together with its whole family and the access
+    /// tokens derived from it. Unknown tokens and tokens of other clients are ignored, as the
+    /// response must not tell them apart.
+    pub async fn revoke(
+        &self,
+        credentials: &ClientCredentials,
+        params: &RevocationParams,
+    ) -> Result<(), AccessTokenError> {
+        let client = self.authenticate_client(credentials).await?;
+
+        // tokens that cannot be decoded, like ID tokens, are not revocable and thus ignored
+        let claims = match self.token_service.verify_any(&params.token) {
+            Ok(claims) => claims,
+            Err(error) => {
+                tracing::info!(
+                    client_id = client.client_id,
+                    error = %error,
+                    "ignored revocation of an undecodable token"
+                );
+                return Ok(());
+            }
+        };
+
+        if claims.client_id() != client.client_id {
+            tracing::warn!(
+                client_id = client.client_id,
+                token.client_id = claims.client_id(),
+                "client tried to revoke a token issued to another client"
+            );
+            return Ok(());
+        }
+
+        match (&claims.jwt_type, claims.family_id) {
+            (JwtType::AccessToken, _) => self.token_service.revoke(&claims).await?,
+            (JwtType::RefreshToken, Some(family_id)) => {
+                self.refresh_token_service.revoke(family_id).await?;
+                self.token_service
+                    .revoke_family(family_id, Self::access_token_expiry())
+                    .await?;
+            }
+            _ => {}
+        }
+
+        Ok(())
+    }
+
+    pub async fn userinfo(&self, access_token: &str) -> Result<UserInfo, UserInfoError> {
+        let claims = self
+            .token_service
+            .verify_own_access_token(access_token)
+            .await?;
+
+        let scope = claims.scope.as_deref();
+        if !scope::contains(scope, "openid") {
+            return Err(UserInfoError::InsufficientScope);
+        }
+
+        let user_id = claims
+            .user_id()
+            .ok_or(UserInfoError::InvalidToken("token subject is not a user"))?;
+        let user = self
+            .user_service
+            .get_by_id(user_id)
+            .await?
+            .ok_or(UserInfoError::InvalidToken("token subject is not a user"))?;
+
+        let (email, email_verified) = if scope::contains(scope, "email") {
+            (Some(user.email), Some(user.activated_at.is_some()))
+        } else {
+            (None, None)
+        };
+
+        Ok(UserInfo {
+            sub: user.id.to_string(),
+            preferred_username: scope::contains(scope, "profile").then_some(user.username),
+            email,
+            email_verified,
+        })
+    }
+
+    /// Issues an access token on behalf of the client itself; its subject is the client's id.
+    async fn client_credentials_flow(
+        &self,
+        client: &Client,
+        token_params: &TokenParams,
+    ) -> Result<AccessToken, AccessTokenError> {
+        // there is no user to identify
+        if scope::contains(token_params.scope.as_deref(), "openid") {
+            return Err(AccessTokenError::InvalidScope("openid".to_string()));
+        }
+        let scope = match self
+            .grant_scope(client, token_params.scope.as_deref())
+            .await
+        {
+            Ok(scope) => scope,
+            Err(ScopeError::NotAllowed(scope)) => {
+                return Err(AccessTokenError::InvalidScope(scope))
+            }
+            Err(ScopeError::InternalError(e)) => return Err(e.into()),
+        };
+        let resource = self
+            .resolve_resource(token_params.resource.as_deref(), None)
+            .await?;
+
+        let expiry = Self::access_token_expiry();
         let token = self.token_service.create_access_token(
-            token_params.client_id.clone(),
-            claims.sub,
+            client.client_id.clone(),
+            client.client_id.clone(),
+            scope.clone(),
+            None,
+            resource,
+            expiry,
+        )?;
+
+        Ok(AccessToken {
+            access_token: token,
+            token_type: "Bearer",
+            expires_in: expiry.num_seconds() as usize,
+            refresh_token: None,
+            scope,
+            id_token: None,
+            issued_token_type: None,
+        })
+    }
+
+    /// Exchanges an access token for one with a narrower scope or another audience (RFC 8693).
+    /// With an actor token, the client acts on behalf of the subject and the actor is recorded in
+    /// the `act` claim; without one, the client impersonates the subject.
+    async fn token_exchange_flow(
+        &self,
+        client: &Client,
+        token_params: &TokenParams,
+    ) -> Result<AccessToken, AccessTokenError> {
+        let subject_token = token_params
+            .subject_token
+            .as_deref()
+            .ok_or(AccessTokenError::MissingParameter("subject_token"))?;
+        let subject_token_type = token_params
+            .subject_token_type
+            .as_deref()
+            .ok_or(AccessTokenError::MissingParameter("subject_token_type"))?;
+        if subject_token_type != Self::ACCESS_TOKEN_TYPE {
+            return Err(AccessTokenError::InvalidRequest(
+                "unsupported subject_token_type",
+            ));
+        }
+        if token_params
+            .requested_token_type
+            .as_deref()
+            .is_some_and(|token_type| token_type != Self::ACCESS_TOKEN_TYPE)
+        {
+            return Err(AccessTokenError::InvalidRequest(
+                "unsupported requested_token_type",
+            ));
+        }
+
+        // invalid subject and actor tokens make the request invalid (RFC 8693 section 2.2.2)
+        let invalid_token = |error, description| match error {
+            JwtVerifyError::InternalError(e) => AccessTokenError::InternalError(e),
+            _ => AccessTokenError::InvalidRequest(description),
+        };
+        let subject = self
+            .token_service
+            .verify_access_token(subject_token)
+            .await
+            .map_err(|e| match e {
+                JwtVerifyError::ExpiredToken => expired_subject_token(),
+                e => invalid_token(e, "invalid subject_token"),
+            })?;
+        let remaining = subject_lifetime(&subject)?;
+        let actor = match (
+            token_params.actor_token.as_deref(),
+            token_params.actor_token_type.as_deref(),
+        ) {
+            (Some(actor_token), Some(Self::ACCESS_TOKEN_TYPE)) => {
+                let actor = self
+                    .token_service
+                    .verify_access_token(actor_token)
+                    .await
+                    .map_err(|e| invalid_token(e, "invalid actor_token"))?;
+                // clients can only act as themselves, or as users who gave them a token
+                if actor.client_id() != client.client_id {
+                    return Err(AccessTokenError::InvalidRequest(
+                        "actor_token was issued to another client",
+                    ));
+                }
+                Some(actor)
+            }
+            (Some(_), Some(_)) => {
+                return Err(AccessTokenError::InvalidRequest(
+                    "unsupported actor_token_type",
+                ))
+            }
+            (Some(_), None) => return Err(AccessTokenError::MissingParameter("actor_token_type")),
+            (None, Some(_)) => return Err(AccessTokenError::MissingParameter("actor_token")),
+            (None, None) => None,
+        };
+
+        // audiences are the resource indicators of registered resource servers
+        let requested = match (
+            token_params.audience.as_deref(),
+            token_params.resource.as_deref(),
+        ) {
+            (Some(audience), Some(resource)) if audience != resource => {
+                return Err(AccessTokenError::InvalidTarget(
+                    "audience and resource do not match",
+                ))
+            }
+            (audience, resource) => resource.or(audience),
+        };
+        let resource = match requested {
+            Some(_) => self.resolve_resource(requested, None).await?,
+            None => (subject.aud != self.token_service.issuer()).then(|| subject.aud.clone()),
+        };
+
+        // the exchanged token only carries scopes the exchanging client may request
+        let scope = match token_params.scope.as_deref() {
+            Some(requested) => {
+                let scope = Self::narrow_scope(Some(requested), subject.scope.as_deref())?;
+                match self.grant_scope(client, scope.as_deref()).await {
+                    Ok(scope) => scope,
+                    Err(ScopeError::NotAllowed(scope)) => {
+                        return Err(AccessTokenError::InvalidScope(scope))
+                    }
+                    Err(ScopeError::InternalError(e)) => return Err(e.into()),
+                }
+            }
+            None => self.allowed_scope(client, subject.scope.clone()).await?,
+        };
+
+        if !self
+            .token_exchange_policy_service
+            .is_allowed(client, subject.client_id(), resource.as_deref())
+            .await?
+        {
+            tracing::warn!(
+                client_id = client.client_id,
+                subject.client_id = subject.client_id(),
+                resource,
+                "token exchange not allowed by policy"
+            );
+            return Err(AccessTokenError::UnauthorizedClient);
+        }
+
+        let act = match actor {
+            Some(actor) => Some(Actor {
+                sub: actor.sub.clone(),
+                client_id: Some(actor.client_id().to_string()),
+                act: subject.act.clone().map(Box::new),
+            }),
+            None => subject.act.clone(),
+        };
+
+        // the exchanged token must not outlive the subject token
+        let expiry = Self::access_token_expiry().min(remaining);
+        let token = self.token_service.create_exchanged_token(
+            &subject,
+            client.client_id.clone(),
+            scope.clone(),
+            resource,
+            act,
             expiry,
         )?;
 
@@ -113,7 +965,56 @@ impl Oauth2Service {
             token_type: "Bearer",
             expires_in: expiry.num_seconds() as usize,
             refresh_token: None,
-            scope: None,
+            scope,
+            id_token: None,
+            issued_token_type: Some(Self::ACCESS_TOKEN_TYPE),
+        })
+    }
+
+    /// Issues an access token for `scope` and the current refresh token of `family`, plus an ID
+    /// token when `scope` includes `openid`.
+    async fn issue_tokens(
+        &self,
+        family: &RefreshTokenFamily,
+        scope: Option<String>,
+        resource: Option<String>,
+        nonce: Option<String>,
+    ) -> Result<AccessToken, AccessTokenError> {
+        let expiry = Self::access_token_expiry();
+        let token = self.token_service.create_access_token(
+            family.client_id.clone(),
+            family.user_id.to_string(),
+            scope.clone(),
+            Some(family.id),
+            resource,
+            expiry,
+        )?;
+
+        let refresh_token = self.token_service.create_refresh_token(family)?;
+
+        let id_token = if scope::contains(scope.as_deref(), "openid") {
+            let user = self
+                .user_service
+                .get_by_id(family.user_id)
+                .await?
+                .ok_or(AccessTokenError::UserNotFound)?;
+
+            Some(
+                self.token_service
+                    .create_id_token(family, &user, nonce, &token, expiry)?,
+            )
+        } else {
+            None
+        };
+
+        Ok(AccessToken {
+            access_token: token,
+            token_type: "Bearer",
+            expires_in: expiry.num_seconds() as usize,
+            refresh_token: Some(refresh_token),
+            scope,
+            id_token,
+            issued_token_type: None,
         })
     }
 }
@@ -122,22 +1023,141 @@ impl Oauth2Service {
 pub enum AccessTokenError {
     #[error("unsupported grant type")]
     UnsupportedGrantType,
+    #[error("missing parameter: {0}")]
+    MissingParameter(&'static str),
+    #[error("invalid request: {0}")]
+    InvalidRequest(&'static str),
+    #[error("invalid target: {0}")]
+    InvalidTarget(&'static str),
     #[error("client authentication failed")]
     ClientAuthenticationFailed,
+    /// Client authentication failed for a client that used HTTP Basic authentication, which is
+    /// answered with a Basic challenge.
+    #[error("client authentication failed")]
+    BasicAuthenticationFailed,
+    #[error("grant type not allowed for the client")]
+    UnauthorizedClient,
     #[error("token audience mismatch")]
     TokenAudienceMismatch,
     #[error("redirect uri mismatch")]
     RedirectUriMismatch,
     #[error("authorization code already used")]
     AuthorizationCodeUsed,
+    #[error("code verifier mismatch")]
+    CodeVerifierMismatch,
+    #[error("code challenge required")]
+    CodeChallengeRequired,
     #[error("token type mismatch")]
     TokenTypeMismatch,
+    #[error("user not found")]
+    UserNotFound,
+    #[error("refresh token reused")]
+    RefreshTokenReused,
+    #[error("refresh token revoked")]
+    RefreshTokenRevoked,
+    #[error("scope not allowed: {0}")]
+    InvalidScope(String),
+    #[error("authorization pending")]
+    AuthorizationPending,
+    #[error("slow down")]
+    SlowDown,
+    #[error("access denied")]
+    AccessDenied,
+    #[error("expired token")]
+    ExpiredToken,
     #[error("invalid token")]
     InvalidToken(#[from] JwtVerifyError),
     #[error("internal error: {0}")]
     InternalError(InternalError),
 }
 
+#[derive(Debug, thiserror::Error)]
+pub enum ScopeError {
+    #[error("scope not allowed: {0}")]
+    NotAllowed(String),
+    #[error("internal error: {0}")]
+    InternalError(InternalError),
+}
+
+impl<T: Into<InternalError>> From<T> for ScopeError {
+    fn from(error: T) -> Self {
+        ScopeError::InternalError(error.into())
+    }
+}
+
+fn expired_subject_token() -> AccessTokenError {
+    AccessTokenError::InvalidRequest("expired subject_token")
+}
+
+/// Time left before the subject token expires. Tokens are accepted within the validation leeway,
+/// but expired ones cannot be exchanged.
+fn subject_lifetime(subject: &Claims) -> Result<chrono::Duration, AccessTokenError> {
+    let remaining = chrono::Duration::seconds(subject.exp as i64 - chrono::Utc::now().timestamp());
+    if remaining <= chrono::Duration::zero() {
+        return Err(expired_subject_token());
+    }
+
+    Ok(remaining)
+}
+
+/// Bearer token errors of the UserInfo and profile endpoints, reported through `WWW-Authenticate`
+/// (RFC 6750).
+#[derive(Debug, thiserror::Error)]
+pub enum UserInfoError {
+    #[error("missing access token")]
+    MissingToken,
+    #[error("malformed authorization header")]
+    InvalidRequest,
+    #[error("invalid token: {0}")]
+    InvalidToken(&'static str),
+    #[error("insufficient scope")]
+    InsufficientScope,
+    #[error("internal error: {0}")]
+    InternalError(InternalError),
+}
+
+impl From<JwtVerifyError> for UserInfoError {
+    fn from(error: JwtVerifyError) -> Self {
+        match error {
+            JwtVerifyError::InvalidToken => UserInfoError::InvalidToken("invalid token"),
+            JwtVerifyError::ExpiredToken => UserInfoError::InvalidToken("expired token"),
+            JwtVerifyError::InternalError(e) => UserInfoError::InternalError(e),
+        }
+    }
+}
+
+impl<T: Into<InternalError>> From<T> for UserInfoError {
+    fn from(error: T) -> Self {
+        UserInfoError::InternalError(error.into())
+    }
+}
+
+impl IntoResponse for UserInfoError {
+    fn into_response(self) -> Response {
+        let (status, challenge) = match self {
+            UserInfoError::MissingToken => (StatusCode::UNAUTHORIZED, "Bearer".to_string()),
+            UserInfoError::InvalidRequest => (
+                StatusCode::BAD_REQUEST,
+                r#"Bearer error="invalid_request""#.to_string(),
+            ),
+            UserInfoError::InvalidToken(description) => (
+                StatusCode::UNAUTHORIZED,
+                format!(
+                    r#"Bearer error="invalid_token", error_description="{}""#,
+                    description
+                ),
+            ),
+            UserInfoError::InsufficientScope => (
+                StatusCode::FORBIDDEN,
+                r#"Bearer error="insufficient_scope", scope="openid""#.to_string(),
+            ),
+            UserInfoError::InternalError(e) => return e.into_response(),
+        };
+
+        (status, [(header::WWW_AUTHENTICATE, challenge)]).into_response()
+    }
+}
+
 impl<T: Into<InternalError>> From<T> for AccessTokenError {
     fn from(error: T) -> Self {
         AccessTokenError::InternalError(error.into())
@@ -146,8 +1166,8 @@ impl<T: Into<InternalError>> From<T> for AccessTokenError {
 
 #[derive(Serialize)]
 pub struct OauthErrorResponse {
-    error: &'static str,
-    error_description: Option<&'static str>,
+    pub error: &'static str,
+    pub error_description: Option<&'static str>,
 }
 
 impl IntoResponse for AccessTokenError {
@@ -161,6 +1181,30 @@ impl IntoResponse for AccessTokenError {
                 }),
             )
                 .into_response(),
+            AccessTokenError::MissingParameter(parameter) => (
+                StatusCode::BAD_REQUEST,
+                Json(OauthErrorResponse {
+                    error: "invalid_request",
+                    error_description: Some(parameter),
+                }),
+            )
+                .into_response(),
+            AccessTokenError::InvalidRequest(description) => (
+                StatusCode::BAD_REQUEST,
+                Json(OauthErrorResponse {
+                    error: "invalid_request",
+                    error_description: Some(description),
+                }),
+            )
+                .into_response(),
+            AccessTokenError::InvalidTarget(description) => (
+                StatusCode::BAD_REQUEST,
+                Json(OauthErrorResponse {
+                    error: "invalid_target",
+                    error_description: Some(description),
+                }),
+            )
+                .into_response(),
             AccessTokenError::ClientAuthenticationFailed => (
                 StatusCode::UNAUTHORIZED,
                 Json(OauthErrorResponse {
@@ -169,6 +1213,23 @@ impl IntoResponse for AccessTokenError {
                 }),
             )
                 .into_response(),
+            AccessTokenError::BasicAuthenticationFailed => (
+                StatusCode::UNAUTHORIZED,
+                [(header::WWW_AUTHENTICATE, r#"Basic realm="sso""#)],
+                Json(OauthErrorResponse {
+                    error: "invalid_client",
+                    error_description: None,
+                }),
+            )
+                .into_response(),
+            AccessTokenError::UnauthorizedClient => (
+                StatusCode::BAD_REQUEST,
+                Json(OauthErrorResponse {
+                    error: "unauthorized_client",
+                    error_description: None,
+                }),
+            )
+                .into_response(),
             AccessTokenError::TokenTypeMismatch => (
                 StatusCode::BAD_REQUEST,
                 Json(OauthErrorResponse {
@@ -201,6 +1262,86 @@ impl IntoResponse for AccessTokenError {
                 }),
             )
                 .into_response(),
+            AccessTokenError::CodeVerifierMismatch => (
+                StatusCode::BAD_REQUEST,
+                Json(OauthErrorResponse {
+                    error: "invalid_grant",
+                    error_description: Some("code verifier mismatch"),
+                }),
+            )
+                .into_response(),
+            AccessTokenError::CodeChallengeRequired => (
+                StatusCode::BAD_REQUEST,
+                Json(OauthErrorResponse {
+                    error: "invalid_grant",
+                    error_description: Some("code challenge required"),
+                }),
+            )
+                .into_response(),
+            AccessTokenError::UserNotFound => (
+                StatusCode::BAD_REQUEST,
+                Json(OauthErrorResponse {
+                    error: "invalid_grant",
+                    error_description: Some("user not found"),
+                }),
+            )
+                .into_response(),
+            AccessTokenError::RefreshTokenReused => (
+                StatusCode::BAD_REQUEST,
+                Json(OauthErrorResponse {
+                    error: "invalid_grant",
+                    error_description: Some("refresh token already used"),
+                }),
+            )
+                .into_response(),
+            AccessTokenError::RefreshTokenRevoked => (
+                StatusCode::BAD_REQUEST,
+                Json(OauthErrorResponse {
+                    error: "invalid_grant",
+                    error_description: Some("refresh token revoked"),
+                }),
+            )
+                .into_response(),
+            AccessTokenError::InvalidScope(_) => (
+                StatusCode::BAD_REQUEST,
+                Json(OauthErrorResponse {
+                    error: "invalid_scope",
+                    error_description: Some("requested scope is not allowed"),
+                }),
+            )
+                .into_response(),
+            AccessTokenError::AuthorizationPending => (
+                StatusCode::BAD_REQUEST,
+                Json(OauthErrorResponse {
+                    error: "authorization_pending",
+                    error_description: None,
+                }),
+            )
+                .into_response(),
+            AccessTokenError::SlowDown => (
+                StatusCode::BAD_REQUEST,
+                Json(OauthErrorResponse {
+                    error: "slow_down",
+                    error_description: None,
+                }),
+            )
+                .into_response(),
+            AccessTokenError::AccessDenied => (
+                StatusCode::BAD_REQUEST,
+                Json(OauthErrorResponse {
+                    error: "access_denied",
+                    error_description: None,
+                }),
+            )
+                .into_response(),
+            AccessTokenError::ExpiredToken => (
+                StatusCode::BAD_REQUEST,
+                Json(OauthErrorResponse {
+                    error: "expired_token",
+                    error_description: None,
+                }),
+            )
+                .into_response(),
             AccessTokenError::InvalidToken(e) => match e {
                 JwtVerifyError::InvalidToken => (
                     StatusCode::BAD_REQUEST,
@@ -224,3 +1365,39 @@ impl IntoResponse for AccessTokenError {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn access_token_claims(exp: i64) -> Claims {
+        serde_json::from_value(json!({
+            "jwt_type": "access_token",
+            "aud": "https://sso.example",
+            "exp": exp,
+            "iat": exp - 60 * 5,
+            "iss": "https://sso.example",
+            "sub": "app",
+        }))
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn expired_subject_tokens_are_invalid_requests() {
+        let now = chrono::Utc::now().timestamp();
+        assert!(subject_lifetime(&access_token_claims(now + 60)).is_ok());
+
+        let response = subject_lifetime(&access_token_claims(now - 10))
+            .unwrap_err()
+            .into_response();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["error"], "invalid_request");
+        assert_eq!(body["error_description"], "expired subject_token");
+    }
+}
@@ -0,0 +1,231 @@
+use crate::helpers::TokenHeader;
+use crate::services::oauth2::AccessTokenError;
+use crate::services::tokens::jwt;
+use serde::Deserialize;
+
+/// Client credentials that may be sent in the request body.
+#[derive(Deserialize)]
+pub struct ClientAuthParams {
+    client_id: Option<String>,
+    client_secret: Option<String>,
+    client_assertion_type: Option<String>,
+    client_assertion: Option<String>,
+}
+
+impl ClientAuthParams {
+    /// For requests whose own parameters carry the client id, like pushed authorization requests.
+    pub fn set_client_id(mut self, client_id: String) -> Self {
+        self.client_id = Some(client_id);
+        self
+    }
+}
+
+/// Credentials a client authenticated with, by authentication method.
+pub enum ClientCredentials {
+    /// HTTP Basic authentication (RFC 6749 section 2.3.1).
+    Basic {
+        client_id: String,
+        client_secret: String,
+    },
+    /// Credentials in the request body.
+    Post {
+        client_id: String,
+        client_secret: String,
+    },
+    /// A JWT signed with the client's private key (RFC 7523 section 2.2).
+    PrivateKeyJwt {
+        client_id: String,
+        client_assertion: String,
+    },
+}
+
+impl ClientCredentials {
+    pub const JWT_BEARER_ASSERTION_TYPE: &'static str =
+        "urn:ietf:params:oauth:client-assertion-type:jwt-bearer";
+
+    /// Picks the credentials of a request. Clients must use exactly one authentication method.
+    pub fn from_request(
+        header: Option<&TokenHeader>,
+        params: &ClientAuthParams,
+    ) -> Result<Self, AccessTokenError> {
+        let header = header.filter(|header| header.is_basic());
+        if let Some(client_assertion) = &params.client_assertion {
+            if header.is_some() || params.client_secret.is_some() {
+                return Err(AccessTokenError::InvalidRequest(
+                    "multiple client authentication methods",
+                ));
+            }
+            if params.client_assertion_type.as_deref() != Some(Self::JWT_BEARER_ASSERTION_TYPE) {
+                return Err(AccessTokenError::InvalidRequest(
+                    "unsupported client_assertion_type",
+                ));
+            }
+            // the assertion is issued by the client about itself
+            let client_id = jwt::unverified_subject(client_assertion)
+                .ok_or(AccessTokenError::ClientAuthenticationFailed)?;
+            if params.client_id.as_ref().is_some_and(|id| *id != client_id) {
+                return Err(AccessTokenError::InvalidRequest("client_id mismatch"));
+            }
+
+            return Ok(Self::PrivateKeyJwt {
+                client_id,
+                client_assertion: client_assertion.clone(),
+            });
+        }
+
+        match header {
+            Some(header) => {
+                if params.client_secret.is_some() {
+                    return Err(AccessTokenError::InvalidRequest(
+                        "multiple client authentication methods",
+                    ));
+                }
+                let (client_id, client_secret) = header
+                    .to_basic_credentials()
+                    .map_err(|_| AccessTokenError::BasicAuthenticationFailed)?;
+                if params.client_id.as_ref().is_some_and(|id| *id != client_id) {
+                    return Err(AccessTokenError::InvalidRequest("client_id mismatch"));
+                }
+
+                Ok(Self::Basic {
+                    client_id,
+                    client_secret,
+                })
+            }
+            None => match (&params.client_id, &params.client_secret) {
+                (Some(client_id), Some(client_secret)) => Ok(Self::Post {
+                    client_id: client_id.clone(),
+                    client_secret: client_secret.clone(),
+                }),
+                _ => Err(AccessTokenError::ClientAuthenticationFailed),
+            },
+        }
+    }
+
+    pub fn client_id(&self) -> &str {
+        match self {
+            Self::Basic { client_id, .. }
+            | Self::Post { client_id, .. }
+            | Self::PrivateKeyJwt { client_id, .. } => client_id,
+        }
+    }
+
+    /// Error for credentials that do not authenticate the client. Only clients that tried HTTP
+    /// Basic authentication are challenged to retry it.
+    pub fn authentication_failed(&self) -> AccessTokenError {
+        match self {
+            Self::Basic { .. } => AccessTokenError::BasicAuthenticationFailed,
+            Self::Post { .. } | Self::PrivateKeyJwt { .. } => {
+                AccessTokenError::ClientAuthenticationFailed
+            }
+        }
+    }
+
+    /// Name of the method as registered in `token_endpoint_auth_method`.
+    pub fn method(&self) -> &'static str {
+        match self {
+            Self::Basic { .. } => "client_secret_basic",
+            Self::Post { .. } => "client_secret_post",
+            Self::PrivateKeyJwt { .. } => "private_key_jwt",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::extract::FromRequestParts;
+    use axum::http::Request;
+    use base64::engine::general_purpose::STANDARD;
+    use base64::Engine;
+
+    async fn header(value: String) -> TokenHeader {
+        let (mut parts, _) = Request::builder()
+            .header("Authorization", value)
+            .body(())
+            .unwrap()
+            .into_parts();
+        TokenHeader::from_request_parts(&mut parts, &())
+            .await
+            .unwrap()
+    }
+
+    async fn basic(client_id: &str, client_secret: &str) -> TokenHeader {
+        header(format!(
+            "Basic {}",
+            STANDARD.encode(format!("{}:{}", client_id, client_secret))
+        ))
+        .await
+    }
+
+    fn params(client_id: Option<&str>, client_secret: Option<&str>) -> ClientAuthParams {
+        ClientAuthParams {
+            client_id: client_id.map(ToString::to_string),
+            client_secret: client_secret.map(ToString::to_string),
+            client_assertion_type: None,
+            client_assertion: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn basic_and_post_credentials_conflict() {
+        let header = basic("app", "secret").await;
+        let credentials =
+            ClientCredentials::from_request(Some(&header), &params(Some("app"), Some("secret")));
+
+        assert!(matches!(
+            credentials,
+            Err(AccessTokenError::InvalidRequest(
+                "multiple client authentication methods"
+            ))
+        ));
+    }
+
+    #[tokio::test]
+    async fn assertion_and_basic_credentials_conflict() {
+        let header = basic("app", "secret").await;
+        let mut params = params(None, None);
+        params.client_assertion_type = Some(ClientCredentials::JWT_BEARER_ASSERTION_TYPE.into());
+        params.client_assertion = Some("assertion".to_string());
+
+        assert!(matches!(
+            ClientCredentials::from_request(Some(&header), &params),
+            Err(AccessTokenError::InvalidRequest(
+                "multiple client authentication methods"
+            ))
+        ));
+    }
+
+    #[tokio::test]
+    async fn basic_client_id_must_match_body() {
+        let header = basic("app", "secret").await;
+        let credentials =
+            ClientCredentials::from_request(Some(&header), &params(Some("other"), None));
+
+        assert!(matches!(
+            credentials,
+            Err(AccessTokenError::InvalidRequest("client_id mismatch"))
+        ));
+    }
+
+    #[tokio::test]
+    async fn malformed_basic_credentials_are_challenged() {
+        let header = header("Basic !!!".to_string()).await;
+        let credentials = ClientCredentials::from_request(Some(&header), &params(None, None));
+
+        assert!(matches!(
+            credentials,
+            Err(AccessTokenError::BasicAuthenticationFailed)
+        ));
+    }
+
+    #[test]
+    fn post_credentials_are_not_challenged() {
+        let credentials = ClientCredentials::from_request(None, &params(Some("app"), None));
+
+        assert!(matches!(
+            credentials,
+            Err(AccessTokenError::ClientAuthenticationFailed)
+        ));
+    }
+}
@@ -0,0 +1,133 @@
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+/// Proof Key for Code Exchange (RFC 7636) challenge bound to an authorization code.
+#[derive(Serialize, Deserialize)]
+pub struct CodeChallenge {
+    pub challenge: String,
+    pub method: CodeChallengeMethod,
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Serialize, Deserialize)]
+pub enum CodeChallengeMethod {
+    #[serde(rename = "plain")]
+    Plain,
+    S256,
+}
+
+impl CodeChallengeMethod {
+    pub fn parse(method: &str) -> Option<Self> {
+        match method {
+            "plain" => Some(Self::Plain),
+            "S256" => Some(Self::S256),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Plain => "plain",
+            Self::S256 => "S256",
+        }
+    }
+}
+
+impl CodeChallenge {
+    /// The method defaults to `plain` when the client does not send one.
+    pub fn new(challenge: String, method: Option<&str>) -> Result<Self, PkceError> {
+        let method = match method {
+            Some(method) => {
+                CodeChallengeMethod::parse(method).ok_or(PkceError::UnsupportedMethod)?
+            }
+            None => CodeChallengeMethod::Plain,
+        };
+
+        if !is_valid_key(&challenge) {
+            return Err(PkceError::InvalidChallenge);
+        }
+
+        Ok(Self { challenge, method })
+    }
+
+    pub fn verify(&self, verifier: &str) -> bool {
+        if !is_valid_key(verifier) {
+            return false;
+        }
+
+        match self.method {
+            CodeChallengeMethod::Plain => verifier == self.challenge,
+            CodeChallengeMethod::S256 => {
+                URL_SAFE_NO_PAD.encode(Sha256::digest(verifier.as_bytes())) == self.challenge
+            }
+        }
+    }
+}
+
+/// Verifiers (and therefore challenges) are 43 to 128 characters from the unreserved set.
+fn is_valid_key(key: &str) -> bool {
+    (43..=128).contains(&key.len())
+        && key
+            .bytes()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, b'-' | b'.' | b'_' | b'~'))
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum PkceError {
+    #[error("code challenge required")]
+    ChallengeRequired,
+    #[error("transform algorithm not supported")]
+    UnsupportedMethod,
+    #[error("invalid code challenge")]
+    InvalidChallenge,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // RFC 7636 appendix B
+    const VERIFIER: &str = "dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXk";
+    const CHALLENGE: &str = "E9Melhoa2OwvFrEMTJguCHaoeK1t8URWbuGJSstw-cM";
+
+    #[test]
+    fn s256_challenge_is_verified() {
+        let challenge = CodeChallenge::new(CHALLENGE.to_string(), Some("S256")).unwrap();
+
+        assert!(challenge.verify(VERIFIER));
+        assert!(!challenge.verify(CHALLENGE));
+    }
+
+    #[test]
+    fn method_defaults_to_plain() {
+        let challenge = CodeChallenge::new(VERIFIER.to_string(), None).unwrap();
+
+        assert_eq!(challenge.method, CodeChallengeMethod::Plain);
+        assert!(challenge.verify(VERIFIER));
+    }
+
+    #[test]
+    fn invalid_challenges_are_rejected() {
+        assert!(matches!(
+            CodeChallenge::new(CHALLENGE.to_string(), Some("S512")),
+            Err(PkceError::UnsupportedMethod)
+        ));
+        assert!(matches!(
+            CodeChallenge::new("too-short".to_string(), Some("S256")),
+            Err(PkceError::InvalidChallenge)
+        ));
+        assert!(matches!(
+            CodeChallenge::new(format!("{}!", &VERIFIER[1..]), None),
+            Err(PkceError::InvalidChallenge)
+        ));
+    }
+
+    #[test]
+    fn malformed_verifier_is_rejected() {
+        let challenge = CodeChallenge::new("a".repeat(43), None).unwrap();
+
+        assert!(!challenge.verify(&"a".repeat(42)));
+        assert!(!challenge.verify(&"a".repeat(129)));
+    }
+}
@@ -0,0 +1,47 @@
+/// Splits a space-delimited scope parameter into its distinct scope tokens, keeping their order.
+pub fn parse(scope: &str) -> Vec<&str> {
+    let mut scopes: Vec<&str> = Vec::new();
+    for scope in scope.split_ascii_whitespace() {
+        if !scopes.contains(&scope) {
+            scopes.push(scope);
+        }
+    }
+
+    scopes
+}
+
+pub fn contains(scope: Option<&str>, token: &str) -> bool {
+    scope.is_some_and(|scope| parse(scope).contains(&token))
+}
+
+/// Joins scope tokens back into a scope parameter, `None` when there is none.
+pub fn join(scopes: &[&str]) -> Option<String> {
+    (!scopes.is_empty()).then(|| scopes.join(" "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_drops_duplicates_and_keeps_order() {
+        assert_eq!(
+            parse("  openid profile\topenid email "),
+            vec!["openid", "profile", "email"]
+        );
+        assert!(parse(" ").is_empty());
+    }
+
+    #[test]
+    fn contains_matches_whole_tokens() {
+        assert!(contains(Some("openid profile"), "profile"));
+        assert!(!contains(Some("openid profile"), "open"));
+        assert!(!contains(None, "openid"));
+    }
+
+    #[test]
+    fn join_of_nothing_is_none() {
+        assert_eq!(join(&["openid", "email"]).as_deref(), Some("openid email"));
+        assert_eq!(join(&[]), None);
+    }
+}
@@ -0,0 +1,77 @@
+use crate::helpers::{random_token, InternalError};
+use crate::kvs::KvsPool;
+use redis::AsyncCommands;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::sync::Arc;
+
+/// Authorization requests pushed by clients (RFC 9126), kept in Redis under a `request_uri` the
+/// client then sends the user's browser to the authorization endpoint with. Verified request
+/// objects are kept the same way while the user logs in.
+pub struct PushedAuthorizationService {
+    kv_pool: Arc<KvsPool>,
+}
+
+impl PushedAuthorizationService {
+    /// Long enough for the user to log in, as the login page keeps passing the `request_uri` on.
+    pub const EXPIRES_IN: u64 = 60 * 10;
+    const REQUEST_URI_PREFIX: &'static str = "urn:ietf:params:oauth:request_uri:";
+
+    pub fn new(kv_pool: Arc<KvsPool>) -> Self {
+        Self { kv_pool }
+    }
+}
+
+impl PushedAuthorizationService {
+    /// Whether `request_uri` refers to a request stored here, rather than to the client.
+    pub fn is_request_uri(request_uri: &str) -> bool {
+        request_uri.starts_with(Self::REQUEST_URI_PREFIX)
+    }
+
+    /// Stores the parameters of an authorization request, returning its `request_uri`.
+    pub async fn push<T: Serialize>(&self, params: &T) -> Result<String, InternalError> {
+        let id = random_token();
+
+        let mut conn = self.kv_pool.get().await?;
+        let _: () = conn
+            .set_ex(
+                format!("pushed_authorization_request:{}", id),
+                serde_json::to_string(params).expect("authorization request is serializable"),
+                Self::EXPIRES_IN,
+            )
+            .await?;
+
+        Ok(format!("{}{}", Self::REQUEST_URI_PREFIX, id))
+    }
+
+    pub async fn get<T: DeserializeOwned>(
+        &self,
+        request_uri: &str,
+    ) -> Result<Option<T>, InternalError> {
+        let Some(id) = request_uri.strip_prefix(Self::REQUEST_URI_PREFIX) else {
+            return Ok(None);
+        };
+
+        let mut conn = self.kv_pool.get().await?;
+        let params: Option<String> = conn
+            .get(format!("pushed_authorization_request:{}", id))
+            .await?;
+
+        Ok(params.and_then(|params| serde_json::from_str(&params).ok()))
+    }
+
+    /// Removes a request as it is used, so its `request_uri` cannot be replayed. Of concurrent
+    /// uses, only the one that removed it gets `true`.
+    pub async fn claim(&self, request_uri: &str) -> Result<bool, InternalError> {
+        let Some(id) = request_uri.strip_prefix(Self::REQUEST_URI_PREFIX) else {
+            return Ok(false);
+        };
+
+        let mut conn = self.kv_pool.get().await?;
+        let deleted: usize = conn
+            .del(format!("pushed_authorization_request:{}", id))
+            .await?;
+
+        Ok(deleted > 0)
+    }
+}
@@ -0,0 +1,252 @@
+use crate::db::DbPool;
+use crate::helpers::InternalError;
+use std::sync::Arc;
+use uuid::Uuid;
+
+pub use models::{NewRefreshTokenFamily, RefreshTokenFamily};
+
+/// Tracks refresh tokens server-side. Every refresh token belongs to a family started by an
+/// authorization grant; only the latest token of a family (`current_jti`) can be redeemed.
+pub struct RefreshTokenService {
+    db_pool: Arc<DbPool>,
+}
+
+pub enum Rotation {
+    Rotated(RefreshTokenFamily),
+    /// The token had already been rotated; the whole family has been revoked.
+    Reused,
+    /// The family does not exist, has expired, or has been revoked.
+    Invalid,
+}
+
+impl RefreshTokenService {
+    pub fn new(db_pool: Arc<DbPool>) -> Self {
+        Self { db_pool }
+    }
+}
+
+impl RefreshTokenService {
+    pub async fn start_family(
+        &self,
+        family: NewRefreshTokenFamily,
+    ) -> Result<RefreshTokenFamily, InternalError> {
+        let mut conn = self.db_pool.get().await?;
+        family.save(&mut conn).await.map_err(Into::into)
+    }
+
+    /// Whether `jti` is the redeemable token of an active family.
+    pub async fn is_current(&self, family_id: Uuid, jti: Uuid) -> Result<bool, InternalError> {
+        let mut conn = self.db_pool.get().await?;
+        let family = RefreshTokenFamily::find_by_id(family_id, &mut conn).await?;
+
+        Ok(family.is_some_and(|family| family.is_active() && family.current_jti == jti))
+    }
+
+    pub async fn revoke(&self, family_id: Uuid) -> Result<(), InternalError> {
+        let mut conn = self.db_pool.get().await?;
+        RefreshTokenFamily::revoke(family_id, &mut conn)
+            .await
+            .map_err(Into::into)
+    }
+
+    /// Revokes the active families of `user_id` for `client_id`, returning their ids.
+    pub async fn revoke_all(
+        &self,
+        user_id: Uuid,
+        client_id: &str,
+    ) -> Result<Vec<Uuid>, InternalError> {
+        let mut conn = self.db_pool.get().await?;
+        RefreshTokenFamily::revoke_all(user_id, client_id, &mut conn)
+            .await
+            .map_err(Into::into)
+    }
+
+    /// Redeems the refresh token `jti` of `family_id`, replacing it with a new one.
+    pub async fn rotate(&self, family_id: Uuid, jti: Uuid) -> Result<Rotation, InternalError> {
+        let mut conn = self.db_pool.get().await?;
+
+        if let Some(family) =
+            RefreshTokenFamily::compare_and_rotate(family_id, jti, Uuid::new_v4(), &mut conn)
+                .await?
+        {
+            return Ok(Rotation::Rotated(family));
+        }
+
+        match RefreshTokenFamily::find_by_id(family_id, &mut conn).await? {
+            Some(family) if family.is_active() => {
+                tracing::warn!(
+                    family.id = family.id.to_string(),
+                    family.client_id,
+                    "refresh token reuse detected, revoking family"
+                );
+                RefreshTokenFamily::revoke(family.id, &mut conn).await?;
+                Ok(Rotation::Reused)
+            }
+            _ => Ok(Rotation::Invalid),
+        }
+    }
+}
+
+mod models {
+    use crate::db::schema::refresh_token_families;
+    use chrono::{DateTime, Utc};
+    use diesel::{
+        ExpressionMethods, Insertable, OptionalExtension, QueryDsl, Queryable, Selectable,
+        SelectableHelper,
+    };
+    use diesel_async::{AsyncPgConnection, RunQueryDsl};
+    use uuid::Uuid;
+
+    #[derive(Debug, Selectable, Queryable)]
+    #[diesel(table_name = refresh_token_families)]
+    pub struct RefreshTokenFamily {
+        pub id: Uuid,
+        pub client_id: String,
+        pub user_id: Uuid,
+        pub scope: Option<String>,
+        pub current_jti: Uuid,
+        pub auth_time: DateTime<Utc>,
+        pub expires_at: DateTime<Utc>,
+        pub revoked_at: Option<DateTime<Utc>>,
+        /// Session the family was started from, for back-channel logout.
+        pub sid: Option<String>,
+        /// Resource indicator the grant was limited to, if any.
+        pub resource: Option<String>,
+    }
+
+    impl RefreshTokenFamily {
+        pub fn is_active(&self) -> bool {
+            self.revoked_at.is_none() && self.expires_at > Utc::now()
+        }
+
+        pub async fn find_by_id(
+            id: Uuid,
+            conn: &mut AsyncPgConnection,
+        ) -> Result<Option<Self>, diesel::result::Error> {
+            refresh_token_families::table
+                .select(Self::as_select())
+                .filter(refresh_token_families::id.eq(id))
+                .first(conn)
+                .await
+                .optional()
+        }
+
+        /// Replaces `current_jti` with `new_jti`, only if the family is still active and
+        /// `current_jti` is still `jti`.
+        pub async fn compare_and_rotate(
+            id: Uuid,
+            jti: Uuid,
+            new_jti: Uuid,
+            conn: &mut AsyncPgConnection,
+        ) -> Result<Option<Self>, diesel::result::Error> {
+            diesel::update(refresh_token_families::table)
+                .filter(refresh_token_families::id.eq(id))
+                .filter(refresh_token_families::current_jti.eq(jti))
+                .filter(refresh_token_families::revoked_at.is_null())
+                .filter(refresh_token_families::expires_at.gt(Utc::now()))
+                .set(refresh_token_families::current_jti.eq(new_jti))
+                .returning(Self::as_select())
+                .get_result(conn)
+                .await
+                .optional()
+        }
+
+        pub async fn revoke(
+            id: Uuid,
+            conn: &mut AsyncPgConnection,
+        ) -> Result<(), diesel::result::Error> {
+            diesel::update(refresh_token_families::table)
+                .filter(refresh_token_families::id.eq(id))
+                .filter(refresh_token_families::revoked_at.is_null())
+                .set(refresh_token_families::revoked_at.eq(Some(Utc::now())))
+                .execute(conn)
+                .await?;
+
+            Ok(())
+        }
+
+        pub async fn revoke_all(
+            user_id: Uuid,
+            client_id: &str,
+            conn: &mut AsyncPgConnection,
+        ) -> Result<Vec<Uuid>, diesel::result::Error> {
+            diesel::update(refresh_token_families::table)
+                .filter(refresh_token_families::user_id.eq(user_id))
+                .filter(refresh_token_families::client_id.eq(client_id))
+                .filter(refresh_token_families::revoked_at.is_null())
+                .set(refresh_token_families::revoked_at.eq(Some(Utc::now())))
+                .returning(refresh_token_families::id)
+                .get_results(conn)
+                .await
+        }
+
+        /// Revokes every active family of `client_id`, returning their ids.
+        pub async fn revoke_client(
+            client_id: &str,
+            conn: &mut AsyncPgConnection,
+        ) -> Result<Vec<Uuid>, diesel::result::Error> {
+            diesel::update(refresh_token_families::table)
+                .filter(refresh_token_families::client_id.eq(client_id))
+                .filter(refresh_token_families::revoked_at.is_null())
+                .set(refresh_token_families::revoked_at.eq(Some(Utc::now())))
+                .returning(refresh_token_families::id)
+                .get_results(conn)
+                .await
+        }
+    }
+
+    #[derive(Debug, Insertable)]
+    #[diesel(table_name = refresh_token_families)]
+    pub struct NewRefreshTokenFamily {
+        client_id: String,
+        user_id: Uuid,
+        scope: Option<String>,
+        current_jti: Uuid,
+        auth_time: DateTime<Utc>,
+        sid: Option<String>,
+        resource: Option<String>,
+        expires_at: DateTime<Utc>,
+    }
+
+    impl NewRefreshTokenFamily {
+        pub fn new(
+            client_id: String,
+            user_id: Uuid,
+            scope: Option<String>,
+            auth_time: DateTime<Utc>,
+            lifetime: chrono::Duration,
+        ) -> Self {
+            Self {
+                client_id,
+                user_id,
+                scope,
+                current_jti: Uuid::new_v4(),
+                auth_time,
+                sid: None,
+                resource: None,
+                expires_at: Utc::now() + lifetime,
+            }
+        }
+
+        pub fn set_sid(mut self, sid: Option<String>) -> Self {
+            self.sid = sid;
+            self
+        }
+
+        pub fn set_resource(mut self, resource: Option<String>) -> Self {
+            self.resource = resource;
+            self
+        }
+
+        pub async fn save(
+            self,
+            conn: &mut AsyncPgConnection,
+        ) -> Result<RefreshTokenFamily, diesel::result::Error> {
+            diesel::insert_into(refresh_token_families::table)
+                .values(self)
+                .returning(RefreshTokenFamily::as_select())
+                .get_result(conn)
+                .await
+        }
+    }
+}
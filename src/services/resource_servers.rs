@@ -0,0 +1,48 @@
+use crate::db::DbPool;
+use crate::helpers::InternalError;
+use std::sync::Arc;
+use url::Url;
+
+/// Registry of the resource servers access tokens can be issued for. A resource server is known
+/// by its resource indicator (RFC 8707), which becomes the `aud` of its access tokens.
+pub struct ResourceServerService {
+    db_pool: Arc<DbPool>,
+}
+
+impl ResourceServerService {
+    pub fn new(db_pool: Arc<DbPool>) -> Self {
+        Self { db_pool }
+    }
+}
+
+impl ResourceServerService {
+    /// Resource indicators must be absolute URIs without a fragment.
+    pub fn is_valid_identifier(identifier: &str) -> bool {
+        Url::parse(identifier).is_ok_and(|url| url.fragment().is_none())
+    }
+
+    /// Resource servers are registered with the `register-resource-server` binary.
+    pub async fn is_registered(&self, identifier: &str) -> Result<bool, InternalError> {
+        let mut conn = self.db_pool.get().await?;
+        models::exists(identifier, &mut conn)
+            .await
+            .map_err(Into::into)
+    }
+}
+
+mod models {
+    use crate::db::schema::resource_servers;
+    use diesel::{ExpressionMethods, QueryDsl};
+    use diesel_async::{AsyncPgConnection, RunQueryDsl};
+
+    pub async fn exists(
+        identifier: &str,
+        conn: &mut AsyncPgConnection,
+    ) -> Result<bool, diesel::result::Error> {
+        diesel::select(diesel::dsl::exists(
+            resource_servers::table.filter(resource_servers::identifier.eq(identifier)),
+        ))
+        .get_result(conn)
+        .await
+    }
+}
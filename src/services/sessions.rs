@@ -0,0 +1,213 @@
+use crate::helpers::{random_token, InternalError};
+use crate::kvs::KvsPool;
+use axum::http::HeaderMap;
+use axum_extra::extract::cookie::{Cookie, Key, SameSite};
+use axum_extra::extract::SignedCookieJar;
+use chrono::{DateTime, Utc};
+use redis::AsyncCommands;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::sync::Arc;
+use uuid::Uuid;
+
+/// Browser sessions of users logged in to the SSO, kept in Redis. A session ends after
+/// `idle_timeout` without use, and at the latest `absolute_timeout` after the user logged in.
+pub struct SessionService {
+    kv_pool: Arc<KvsPool>,
+    cookie_key: Key,
+    idle_timeout: chrono::Duration,
+    absolute_timeout: chrono::Duration,
+    secure_cookie: bool,
+    admin_token: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Session {
+    /// Identifier shared with clients in ID and logout tokens. Unlike the session id in the
+    /// cookie, it is not a secret.
+    pub sid: String,
+    pub user_id: Uuid,
+    pub auth_time: DateTime<Utc>,
+}
+
+impl SessionService {
+    pub const COOKIE_NAME: &'static str = "sso_session";
+
+    pub fn new(kv_pool: Arc<KvsPool>, cookie_key: Key, secure_cookie: bool) -> Self {
+        Self {
+            kv_pool,
+            cookie_key,
+            idle_timeout: chrono::Duration::hours(2),
+            absolute_timeout: chrono::Duration::hours(24),
+            secure_cookie,
+            admin_token: None,
+        }
+    }
+
+    pub fn set_timeouts(
+        mut self,
+        idle_timeout: chrono::Duration,
+        absolute_timeout: chrono::Duration,
+    ) -> Self {
+        self.idle_timeout = idle_timeout;
+        self.absolute_timeout = absolute_timeout;
+        self
+    }
+
+    /// Lets callers presenting `token` end the sessions of any user.
+    pub fn set_admin_token(mut self, token: Option<String>) -> Self {
+        self.admin_token = token;
+        self
+    }
+}
+
+impl SessionService {
+    /// Starts a session for a user who just entered their credentials, returning its id. `sid` is
+    /// kept when the user re-authenticates, so clients see the same session.
+    pub async fn create(
+        &self,
+        user_id: Uuid,
+        sid: Option<String>,
+    ) -> Result<(String, Session), InternalError> {
+        let session_id = random_token();
+        let session = Session {
+            sid: sid.unwrap_or_else(|| Uuid::new_v4().to_string()),
+            user_id,
+            auth_time: Utc::now(),
+        };
+
+        let mut conn = self.kv_pool.get().await?;
+        let _: () = conn
+            .set_ex(
+                format!("session:{}", session_id),
+                serde_json::to_string(&session).expect("session is serializable"),
+                self.idle_timeout.num_seconds() as u64,
+            )
+            .await?;
+        // sessions that ended on their own are left in the index until it expires
+        let user_sessions = format!("user_sessions:{}", user_id);
+        let _: () = conn.sadd(&user_sessions, &session_id).await?;
+        let _: () = conn
+            .expire(&user_sessions, self.absolute_timeout.num_seconds())
+            .await?;
+        tracing::info!(user.id = user_id.to_string(), "session created");
+
+        Ok((session_id, session))
+    }
+
+    /// Returns the session if it is still valid, extending its idle timeout.
+    pub async fn get(&self, session_id: &str) -> Result<Option<Session>, InternalError> {
+        let key = format!("session:{}", session_id);
+
+        let mut conn = self.kv_pool.get().await?;
+        let session: Option<String> = conn.get(&key).await?;
+        let Some(session) = session.and_then(|session| {
+            serde_json::from_str::<Session>(&session)
+                .inspect_err(|e| tracing::warn!(error = %e, "malformed session"))
+                .ok()
+        }) else {
+            return Ok(None);
+        };
+
+        let remaining = session.auth_time + self.absolute_timeout - Utc::now();
+        if remaining <= chrono::Duration::zero() {
+            let _: () = conn.del(&key).await?;
+            return Ok(None);
+        }
+
+        let _: () = conn
+            .expire(&key, remaining.min(self.idle_timeout).num_seconds())
+            .await?;
+
+        Ok(Some(session))
+    }
+
+    pub async fn delete(&self, session_id: &str) -> Result<(), InternalError> {
+        let mut conn = self.kv_pool.get().await?;
+        let _: () = conn.del(format!("session:{}", session_id)).await?;
+
+        Ok(())
+    }
+
+    /// Records that `client_id` received tokens through the session `sid`.
+    pub async fn add_client(&self, sid: &str, client_id: &str) -> Result<(), InternalError> {
+        let key = format!("session_clients:{}", sid);
+
+        let mut conn = self.kv_pool.get().await?;
+        let _: () = conn.sadd(&key, client_id).await?;
+        let _: () = conn
+            .expire(&key, self.absolute_timeout.num_seconds())
+            .await?;
+
+        Ok(())
+    }
+
+    /// Ends the session, returning the clients it was used with.
+    pub async fn end(
+        &self,
+        session_id: &str,
+        session: &Session,
+    ) -> Result<Vec<String>, InternalError> {
+        let key = format!("session_clients:{}", session.sid);
+
+        let mut conn = self.kv_pool.get().await?;
+        let _: () = conn.del(format!("session:{}", session_id)).await?;
+        let _: () = conn
+            .srem(format!("user_sessions:{}", session.user_id), session_id)
+            .await?;
+        let client_ids: Vec<String> = conn.smembers(&key).await?;
+        let _: () = conn.del(&key).await?;
+
+        Ok(client_ids)
+    }
+
+    /// Ends every session of the user, returning each of them with the clients it was used with.
+    pub async fn end_all(
+        &self,
+        user_id: Uuid,
+    ) -> Result<Vec<(Session, Vec<String>)>, InternalError> {
+        let mut conn = self.kv_pool.get().await?;
+        let session_ids: Vec<String> = conn.smembers(format!("user_sessions:{}", user_id)).await?;
+
+        let mut ended = Vec::new();
+        for session_id in session_ids {
+            let session: Option<String> = conn.get(format!("session:{}", session_id)).await?;
+            let Some(session) =
+                session.and_then(|session| serde_json::from_str::<Session>(&session).ok())
+            else {
+                continue;
+            };
+            let client_ids = self.end(&session_id, &session).await?;
+            ended.push((session, client_ids));
+        }
+        let _: () = conn.del(format!("user_sessions:{}", user_id)).await?;
+
+        Ok(ended)
+    }
+
+    pub fn is_admin_token(&self, token: &str) -> bool {
+        // compare digests so that the comparison time says nothing about the token
+        self.admin_token
+            .as_deref()
+            .is_some_and(|expected| Sha256::digest(expected) == Sha256::digest(token))
+    }
+
+    /// Cookies of the request, only keeping the ones signed with the session key.
+    pub fn cookie_jar(&self, headers: &HeaderMap) -> SignedCookieJar {
+        SignedCookieJar::from_headers(headers, self.cookie_key.clone())
+    }
+
+    /// Cookie that removes the session cookie from the browser.
+    pub fn removal_cookie(&self) -> Cookie<'static> {
+        Cookie::build(Self::COOKIE_NAME).path("/").build()
+    }
+
+    pub fn cookie(&self, session_id: String) -> Cookie<'static> {
+        Cookie::build((Self::COOKIE_NAME, session_id))
+            .path("/")
+            .http_only(true)
+            .secure(self.secure_cookie)
+            .same_site(SameSite::Lax)
+            .build()
+    }
+}
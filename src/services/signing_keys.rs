@@ -0,0 +1,312 @@
+use crate::db::DbPool;
+use crate::helpers::InternalError;
+use crate::services::tokens::jwt::JwtSigner;
+use crate::services::tokens::key_ring::KeyRing;
+use crate::services::tokens::keys::PrivateKey;
+use crate::services::tokens::TokenService;
+use chrono::{DateTime, Utc};
+use diesel_async::scoped_futures::ScopedFutureExt;
+use diesel_async::{AsyncConnection, AsyncPgConnection};
+use jsonwebtoken::Algorithm;
+use std::sync::Arc;
+
+/// Persists the token signing keys in Postgres and keeps the token service's key ring in sync
+/// with it, so that every instance signs with the same key and rotation needs no restart.
+pub struct SigningKeyService {
+    db_pool: Arc<DbPool>,
+    token_service: Arc<TokenService>,
+    algorithm: Algorithm,
+    rotation_interval: Option<chrono::Duration>,
+    prepublish: chrono::Duration,
+    retention: chrono::Duration,
+}
+
+impl SigningKeyService {
+    pub fn new(
+        db_pool: Arc<DbPool>,
+        token_service: Arc<TokenService>,
+        algorithm: Algorithm,
+    ) -> Self {
+        Self {
+            db_pool,
+            token_service,
+            algorithm,
+            rotation_interval: None,
+            prepublish: chrono::Duration::hours(24),
+            retention: chrono::Duration::days(30),
+        }
+    }
+
+    /// Rotates the signing key automatically once it is older than `interval`. The new key is
+    /// published `prepublish` before it starts signing, and superseded keys keep verifying tokens
+    /// for `retention` after that.
+    pub fn set_rotation(
+        mut self,
+        interval: Option<chrono::Duration>,
+        prepublish: chrono::Duration,
+        retention: chrono::Duration,
+    ) -> Self {
+        self.rotation_interval = interval;
+        self.prepublish = prepublish;
+        self.retention = retention;
+        self
+    }
+}
+
+impl SigningKeyService {
+    /// Loads the key ring, creating the first signing key from `initial_key` (or a freshly
+    /// generated one) when there is no active key yet.
+    pub async fn bootstrap(
+        &self,
+        initial_key: Option<(PrivateKey, Option<String>)>,
+    ) -> Result<(), InternalError> {
+        let mut conn = self.db_pool.get().await?;
+        conn.transaction(|conn| {
+            async move {
+                // instances starting together must not each create a key
+                models::SigningKey::lock(conn).await?;
+                let now = Utc::now();
+
+                let keys = models::SigningKey::find_unretired(now, conn).await?;
+                if !keys.iter().any(|key| key.activates_at <= now) {
+                    let (key, kid) = match initial_key {
+                        Some(key) => key,
+                        None => (PrivateKey::generate(self.algorithm)?, None),
+                    };
+                    let kid = kid.unwrap_or_else(|| key.thumbprint());
+
+                    tracing::info!(kid, "creating initial signing key");
+                    models::NewSigningKey::new(kid, key.to_pem()?, now)
+                        .save(conn)
+                        .await?;
+                } else if initial_key.is_some() {
+                    tracing::warn!(
+                        "ignoring JWT_SIGNING_KEY, an active signing key already exists; \
+                         use rotate-signing-key to replace it"
+                    );
+                }
+
+                Ok::<_, InternalError>(())
+            }
+            .scope_boxed()
+        })
+        .await?;
+
+        self.reload().await
+    }
+
+    pub async fn reload(&self) -> Result<(), InternalError> {
+        let mut conn = self.db_pool.get().await?;
+        let keys = models::SigningKey::find_unretired(Utc::now(), &mut conn).await?;
+
+        let mut key_ring = KeyRing::default();
+        for key in keys {
+            let private_key = PrivateKey::from_pem(&key.private_key)?;
+            key_ring.insert(
+                JwtSigner::from_private_key(&private_key, Some(key.kid))?,
+                key.activates_at,
+            );
+        }
+
+        self.token_service.set_key_ring(key_ring);
+        Ok(())
+    }
+
+    /// Adds a new signing key that takes over at `activates_at`. Keys that activate before it are
+    /// retired `retention` after the new key activates.
+    pub async fn rotate(&self, activates_at: DateTime<Utc>) -> Result<String, InternalError> {
+        let key = PrivateKey::generate(self.algorithm)?;
+
+        let mut conn = self.db_pool.get().await?;
+        let kid = conn
+            .transaction(|conn| {
+                async move {
+                    models::SigningKey::lock(conn).await?;
+                    self.add_key(key, activates_at, conn).await
+                }
+                .scope_boxed()
+            })
+            .await?;
+
+        self.reload().await?;
+        Ok(kid)
+    }
+
+    /// Schedules a rotation when the newest key is older than the rotation interval, and drops
+    /// keys past their retention.
+    async fn rotate_if_due(&self) -> Result<(), InternalError> {
+        let mut conn = self.db_pool.get().await?;
+        conn.transaction(|conn| {
+            async move {
+                // every instance runs this, only one may rotate
+                models::SigningKey::lock(conn).await?;
+                let now = Utc::now();
+
+                let deleted = models::SigningKey::delete_retired(now, conn).await?;
+                if deleted > 0 {
+                    tracing::info!(count = deleted, "retired signing keys deleted");
+                }
+
+                if let Some(interval) = self.rotation_interval {
+                    let newest = models::SigningKey::find_unretired(now, conn)
+                        .await?
+                        .into_iter()
+                        .map(|key| key.activates_at)
+                        .max();
+                    let rotation_due = match newest {
+                        Some(activates_at) => activates_at + interval <= now,
+                        None => true,
+                    };
+
+                    if rotation_due {
+                        let key = PrivateKey::generate(self.algorithm)?;
+                        self.add_key(key, now + self.prepublish, conn).await?;
+                    }
+                }
+
+                Ok::<_, InternalError>(())
+            }
+            .scope_boxed()
+        })
+        .await?;
+
+        self.reload().await
+    }
+
+    /// Must be called with the signing key lock held.
+    async fn add_key(
+        &self,
+        key: PrivateKey,
+        activates_at: DateTime<Utc>,
+        conn: &mut AsyncPgConnection,
+    ) -> Result<String, InternalError> {
+        let kid = key.thumbprint();
+
+        models::SigningKey::retire_activating_before(
+            activates_at,
+            activates_at + self.retention,
+            conn,
+        )
+        .await?;
+        models::NewSigningKey::new(kid.clone(), key.to_pem()?, activates_at)
+            .save(conn)
+            .await?;
+        tracing::info!(kid, activates_at = %activates_at, "signing key rotation scheduled");
+
+        Ok(kid)
+    }
+
+    /// Periodically picks up keys added by other instances and performs scheduled rotations.
+    pub async fn run(self: Arc<Self>) {
+        let mut interval = tokio::time::interval(std::time::Duration::from_secs(60));
+        loop {
+            interval.tick().await;
+            if let Err(error) = self.rotate_if_due().await {
+                tracing::error!(error = %error, "failed to refresh signing keys");
+            }
+        }
+    }
+}
+
+mod models {
+    use crate::db::schema::signing_keys;
+    use chrono::{DateTime, Utc};
+    use diesel::sql_types::BigInt;
+    use diesel::{
+        BoolExpressionMethods, ExpressionMethods, Insertable, QueryDsl, Queryable, Selectable,
+        SelectableHelper,
+    };
+    use diesel_async::{AsyncPgConnection, RunQueryDsl};
+
+    /// Advisory lock id, "SIGNKEYS" in ASCII.
+    const LOCK_KEY: i64 = 0x5349_474e_4b45_5953;
+
+    #[derive(Debug, Selectable, Queryable)]
+    pub struct SigningKey {
+        pub kid: String,
+        pub private_key: String,
+        pub activates_at: DateTime<Utc>,
+    }
+
+    impl SigningKey {
+        pub async fn find_unretired(
+            now: DateTime<Utc>,
+            conn: &mut AsyncPgConnection,
+        ) -> Result<Vec<Self>, diesel::result::Error> {
+            signing_keys::table
+                .select(Self::as_select())
+                .filter(
+                    signing_keys::retires_at
+                        .is_null()
+                        .or(signing_keys::retires_at.gt(now)),
+                )
+                .order(signing_keys::activates_at.asc())
+                .load(conn)
+                .await
+        }
+
+        /// Serializes changes to the key ring across instances, until the end of the transaction.
+        pub async fn lock(conn: &mut AsyncPgConnection) -> Result<(), diesel::result::Error> {
+            diesel::sql_query("SELECT pg_advisory_xact_lock($1)")
+                .bind::<BigInt, _>(LOCK_KEY)
+                .execute(conn)
+                .await?;
+
+            Ok(())
+        }
+
+        /// Retires keys that are superseded by a key activating at `activates_at`. Keys scheduled
+        /// after it keep going.
+        pub async fn retire_activating_before(
+            activates_at: DateTime<Utc>,
+            retires_at: DateTime<Utc>,
+            conn: &mut AsyncPgConnection,
+        ) -> Result<(), diesel::result::Error> {
+            diesel::update(signing_keys::table)
+                .filter(signing_keys::retires_at.is_null())
+                .filter(signing_keys::activates_at.lt(activates_at))
+                .set(signing_keys::retires_at.eq(Some(retires_at)))
+                .execute(conn)
+                .await?;
+
+            Ok(())
+        }
+
+        pub async fn delete_retired(
+            now: DateTime<Utc>,
+            conn: &mut AsyncPgConnection,
+        ) -> Result<usize, diesel::result::Error> {
+            diesel::delete(signing_keys::table)
+                .filter(signing_keys::retires_at.le(now))
+                .execute(conn)
+                .await
+        }
+    }
+
+    #[derive(Debug, Insertable)]
+    #[diesel(table_name = signing_keys)]
+    pub struct NewSigningKey {
+        kid: String,
+        private_key: String,
+        activates_at: DateTime<Utc>,
+    }
+
+    impl NewSigningKey {
+        pub fn new(kid: String, private_key: String, activates_at: DateTime<Utc>) -> Self {
+            Self {
+                kid,
+                private_key,
+                activates_at,
+            }
+        }
+
+        pub async fn save(self, conn: &mut AsyncPgConnection) -> Result<(), diesel::result::Error> {
+            diesel::insert_into(signing_keys::table)
+                .values(self)
+                .execute(conn)
+                .await?;
+
+            Ok(())
+        }
+    }
+}
@@ -0,0 +1,67 @@
+use crate::db::DbPool;
+use crate::helpers::InternalError;
+use crate::services::clients::Client;
+use std::sync::Arc;
+
+pub use models::TokenExchangePolicy;
+
+/// Policies on which tokens a client may exchange (RFC 8693). A policy lets a client exchange
+/// tokens issued to `subject_client_id` for tokens of `audience`, or of any audience when it is
+/// left out. Clients without a matching policy cannot exchange tokens at all. Policies are added
+/// with the `allow-token-exchange` binary.
+pub struct TokenExchangePolicyService {
+    db_pool: Arc<DbPool>,
+}
+
+impl TokenExchangePolicyService {
+    pub fn new(db_pool: Arc<DbPool>) -> Self {
+        Self { db_pool }
+    }
+}
+
+impl TokenExchangePolicyService {
+    /// Whether `client` may exchange a token issued to `subject_client_id` for a token of
+    /// `audience`, `None` being this service itself.
+    pub async fn is_allowed(
+        &self,
+        client: &Client,
+        subject_client_id: &str,
+        audience: Option<&str>,
+    ) -> Result<bool, InternalError> {
+        let mut conn = self.db_pool.get().await?;
+        let policies =
+            TokenExchangePolicy::find_by_subject(client.id, subject_client_id, &mut conn).await?;
+
+        Ok(policies
+            .iter()
+            .any(|policy| policy.audience.is_none() || policy.audience.as_deref() == audience))
+    }
+}
+
+mod models {
+    use crate::db::schema::token_exchange_policies;
+    use diesel::{ExpressionMethods, QueryDsl, Queryable, Selectable, SelectableHelper};
+    use diesel_async::{AsyncPgConnection, RunQueryDsl};
+    use uuid::Uuid;
+
+    #[derive(Debug, Selectable, Queryable)]
+    #[diesel(table_name = token_exchange_policies)]
+    pub struct TokenExchangePolicy {
+        pub audience: Option<String>,
+    }
+
+    impl TokenExchangePolicy {
+        pub async fn find_by_subject(
+            client_id: Uuid,
+            subject_client_id: &str,
+            conn: &mut AsyncPgConnection,
+        ) -> Result<Vec<Self>, diesel::result::Error> {
+            token_exchange_policies::table
+                .select(Self::as_select())
+                .filter(token_exchange_policies::client_id.eq(client_id))
+                .filter(token_exchange_policies::subject_client_id.eq(subject_client_id))
+                .load(conn)
+                .await
+        }
+    }
+}
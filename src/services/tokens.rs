@@ -1,41 +1,222 @@
 pub mod jwt;
+pub mod key_ring;
+pub mod keys;
 
 use crate::helpers::InternalError;
 use crate::kvs::KvsPool;
-use crate::services::tokens::jwt::{Claims, JwtSigner, JwtType, JwtVerifyError};
+use crate::services::oauth2::AuthorizationRequest;
+use crate::services::refresh_tokens::RefreshTokenFamily;
+use crate::services::sessions::Session;
+use crate::services::tokens::jwt::{
+    Actor, Claims, ClientAssertionClaims, IdTokenClaims, JwtSigner, JwtType, JwtVerifyError,
+    LogoutTokenClaims,
+};
+use crate::services::tokens::key_ring::KeyRing;
+use crate::services::users::User;
+use jsonwebtoken::jwk::JwkSet;
+use jsonwebtoken::Algorithm;
 use redis::{AsyncCommands, ExistenceCheck, SetExpiry, SetOptions};
-use std::sync::Arc;
-
-pub struct JwtSecret<'a>(pub &'a [u8]);
+use serde::Serialize;
+use std::sync::{Arc, RwLock};
 
 pub struct TokenService {
     kv_pool: Arc<KvsPool>,
-    jwt_signer: JwtSigner,
+    key_ring: RwLock<KeyRing>,
+    legacy_signer: Option<JwtSigner>,
+    issuer: String,
 }
 
 impl TokenService {
-    pub fn new(kv_pool: Arc<KvsPool>, JwtSecret(secret): JwtSecret) -> Self {
+    pub fn new(kv_pool: Arc<KvsPool>, issuer: String) -> Self {
         Self {
             kv_pool,
-            jwt_signer: JwtSigner::new(secret),
+            key_ring: RwLock::new(KeyRing::default()),
+            legacy_signer: None,
+            issuer,
         }
     }
+
+    /// Keeps accepting tokens signed by a key without a `kid`, e.g. the HS256 `JWT_SECRET` used
+    /// before the key ring existed. The key is never used to sign new tokens.
+    pub fn set_legacy_signer(mut self, signer: JwtSigner) -> Self {
+        self.legacy_signer = Some(signer);
+        self
+    }
 }
 
 impl TokenService {
+    /// Issuer of the tokens signed with the legacy signer.
+    const LEGACY_ISSUER: &'static str = "agus.dev sso";
+
+    /// Algorithms clients may sign their assertions and request objects with. Symmetric ones
+    /// would need a shared secret, which is what `private_key_jwt` avoids.
+    pub const CLIENT_ASSERTION_ALGORITHMS: &'static [Algorithm] = &[
+        Algorithm::RS256,
+        Algorithm::RS384,
+        Algorithm::RS512,
+        Algorithm::PS256,
+        Algorithm::PS384,
+        Algorithm::PS512,
+        Algorithm::ES256,
+        Algorithm::ES384,
+        Algorithm::EdDSA,
+    ];
+
+    pub fn issuer(&self) -> &str {
+        &self.issuer
+    }
+
+    pub fn set_key_ring(&self, key_ring: KeyRing) {
+        *self.key_ring.write().expect("key ring lock poisoned") = key_ring;
+    }
+
+    pub fn signing_algorithms(&self) -> Vec<Algorithm> {
+        self.key_ring
+            .read()
+            .expect("key ring lock poisoned")
+            .algorithms()
+    }
+
+    /// Public keys that can be used to verify tokens issued by this service.
+    pub fn jwks(&self) -> JwkSet {
+        self.key_ring.read().expect("key ring lock poisoned").jwks()
+    }
+
+    fn sign<T: Serialize>(&self, claims: &T) -> Result<String, InternalError> {
+        self.sign_with_type(claims, "JWT")
+    }
+
+    fn sign_with_type<T: Serialize>(&self, claims: &T, typ: &str) -> Result<String, InternalError> {
+        self.key_ring
+            .read()
+            .expect("key ring lock poisoned")
+            .signing_key()
+            .ok_or(InternalError::NoSigningKey)?
+            .sign_with_type(claims, typ)
+    }
+
     pub fn verify_any(&self, token: &str) -> Result<Claims, JwtVerifyError> {
-        self.jwt_signer.verify(token)
+        self.with_verifier(token, |signer, issuers| signer.verify(token, issuers))
     }
 
-    pub fn verify_access_token(&self, token: &str) -> Result<Claims, JwtVerifyError> {
+    /// Verifies an ID token we issued, even an expired one, as RPs pass them back as hints.
+    pub fn verify_id_token_hint(&self, token: &str) -> Result<IdTokenClaims, JwtVerifyError> {
+        self.with_verifier(token, |signer, issuers| {
+            signer.verify_id_token(token, issuers)
+        })
+    }
+
+    fn with_verifier<T>(
+        &self,
+        token: &str,
+        verify: impl FnOnce(&JwtSigner, &[&str]) -> Result<T, JwtVerifyError>,
+    ) -> Result<T, JwtVerifyError> {
+        let header =
+            jsonwebtoken::decode_header(token).map_err(|_| JwtVerifyError::InvalidToken)?;
+
+        let key_ring = self.key_ring.read().expect("key ring lock poisoned");
+        let (signer, issuers) = match header.kid.as_deref() {
+            Some(kid) => (key_ring.find(kid), vec![self.issuer.as_str()]),
+            // tokens signed before the key ring existed carry the issuer used back then
+            None => (
+                self.legacy_signer.as_ref(),
+                vec![Self::LEGACY_ISSUER, self.issuer.as_str()],
+            ),
+        };
+        let signer = signer.ok_or(JwtVerifyError::InvalidToken)?;
+
+        verify(signer, &issuers)
+    }
+
+    pub async fn verify_access_token(&self, token: &str) -> Result<Claims, JwtVerifyError> {
         let claims = self.verify_any(token)?;
+        self.check_access_token(claims).await
+    }
+
+    /// Verifies an access token presented to our own endpoints, rejecting those issued for a
+    /// resource server.
+    pub async fn verify_own_access_token(&self, token: &str) -> Result<Claims, JwtVerifyError> {
+        let claims = self.verify_any(token)?;
+        // access tokens from before resource indicators have the client as audience
+        if claims.client_id.is_some() && claims.aud != self.issuer {
+            return Err(JwtVerifyError::InvalidToken);
+        }
+
+        self.check_access_token(claims).await
+    }
+
+    async fn check_access_token(&self, claims: Claims) -> Result<Claims, JwtVerifyError> {
         if claims.jwt_type != JwtType::AccessToken {
             return Err(JwtVerifyError::InvalidToken);
         }
 
+        if self
+            .is_revoked(&claims)
+            .await
+            .map_err(JwtVerifyError::InternalError)?
+        {
+            return Err(JwtVerifyError::InvalidToken);
+        }
+
         Ok(claims)
     }
 
+    /// Whether the token, or the refresh token family it was derived from, has been revoked.
+    pub async fn is_revoked(&self, claims: &Claims) -> Result<bool, InternalError> {
+        let mut keys = Vec::new();
+        if let Some(jti) = claims.jti {
+            keys.push(format!("revoked_token:{}", jti));
+        }
+        if let Some(family_id) = claims.family_id {
+            keys.push(format!("revoked_family:{}", family_id));
+        }
+        if keys.is_empty() {
+            return Ok(false);
+        }
+
+        let mut conn = self.kv_pool.get().await?;
+        let revoked: usize = conn.exists(keys).await?;
+
+        Ok(revoked > 0)
+    }
+
+    /// Revokes a single token until it expires.
+    pub async fn revoke(&self, claims: &Claims) -> Result<(), InternalError> {
+        let Some(jti) = claims.jti else {
+            return Ok(());
+        };
+        let ttl = claims.exp as i64 - chrono::Utc::now().timestamp();
+        if ttl <= 0 {
+            return Ok(());
+        }
+
+        let mut conn = self.kv_pool.get().await?;
+        let _: () = conn
+            .set_ex(format!("revoked_token:{}", jti), 1, ttl as u64)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Revokes every token derived from the refresh token family `family_id`. Tokens derived from
+    /// it are short-lived, so the entry only has to outlive `max_token_lifetime`.
+    pub async fn revoke_family(
+        &self,
+        family_id: uuid::Uuid,
+        max_token_lifetime: chrono::Duration,
+    ) -> Result<(), InternalError> {
+        let mut conn = self.kv_pool.get().await?;
+        let _: () = conn
+            .set_ex(
+                format!("revoked_family:{}", family_id),
+                1,
+                max_token_lifetime.num_seconds() as u64,
+            )
+            .await?;
+
+        Ok(())
+    }
+
     pub fn verify_activation_code(&self, token: &str) -> Result<Claims, JwtVerifyError> {
         let claims = self.verify_any(token)?;
         if claims.jwt_type != JwtType::ActivationCode {
@@ -47,41 +228,239 @@ impl TokenService {
 
     pub fn create_authorization_code(
         &self,
-        client_id: String,
-        user_id: uuid::Uuid,
+        session: &Session,
+        request: AuthorizationRequest,
         expiry: chrono::Duration,
     ) -> Result<String, InternalError> {
-        self.jwt_signer.sign(&Claims::new(
+        let mut claims = Claims::new(
             JwtType::AuthorizationCode,
-            client_id,
-            user_id,
+            self.issuer.clone(),
+            request.client_id,
+            session.user_id.to_string(),
             expiry,
-        ))
+        );
+        claims.redirect_uri = Some(request.redirect_uri);
+        claims.redirect_uri_sent = request.redirect_uri_sent;
+        claims.scope = request.scope;
+        claims.nonce = request.nonce;
+        claims.auth_time = Some(session.auth_time.timestamp() as usize);
+        claims.sid = Some(session.sid.clone());
+        claims.resource = request.resource;
+        if let Some(code_challenge) = request.code_challenge {
+            claims.code_challenge = Some(code_challenge.challenge);
+            claims.code_challenge_method = Some(code_challenge.method.as_str().to_string());
+        }
+
+        self.sign(&claims)
     }
 
+    /// Creates an access token following the JWT profile of RFC 9068. Its audience is the
+    /// `resource` it was requested for, or this service itself.
     pub fn create_access_token(
         &self,
         client_id: String,
-        user_id: uuid::Uuid,
+        sub: String,
+        scope: Option<String>,
+        family_id: Option<uuid::Uuid>,
+        resource: Option<String>,
         expiry: chrono::Duration,
     ) -> Result<String, InternalError> {
-        self.jwt_signer.sign(&Claims::new(
+        let mut claims = Claims::new(
             JwtType::AccessToken,
-            client_id,
-            user_id,
+            self.issuer.clone(),
+            resource.unwrap_or_else(|| self.issuer.clone()),
+            sub,
             expiry,
-        ))
+        );
+        claims.client_id = Some(client_id);
+        claims.scope = scope;
+        claims.family_id = family_id;
+
+        self.sign_with_type(&claims, "at+jwt")
+    }
+
+    /// Creates an access token for the subject of `subject`, an access token exchanged by
+    /// `client_id`. It stays in the refresh token family of the subject token, so revoking the
+    /// family also revokes it.
+    pub fn create_exchanged_token(
+        &self,
+        subject: &Claims,
+        client_id: String,
+        scope: Option<String>,
+        resource: Option<String>,
+        act: Option<Actor>,
+        expiry: chrono::Duration,
+    ) -> Result<String, InternalError> {
+        let mut claims = Claims::new(
+            JwtType::AccessToken,
+            self.issuer.clone(),
+            resource.unwrap_or_else(|| self.issuer.clone()),
+            subject.sub.clone(),
+            expiry,
+        );
+        claims.client_id = Some(client_id);
+        claims.scope = scope;
+        claims.family_id = subject.family_id;
+        claims.auth_time = subject.auth_time;
+        claims.act = act;
+
+        self.sign_with_type(&claims, "at+jwt")
+    }
+
+    /// Creates the current refresh token of `family`.
+    pub fn create_refresh_token(
+        &self,
+        family: &RefreshTokenFamily,
+    ) -> Result<String, InternalError> {
+        let mut claims = Claims::new(
+            JwtType::RefreshToken,
+            self.issuer.clone(),
+            family.client_id.clone(),
+            family.user_id.to_string(),
+            family.expires_at - chrono::Utc::now(),
+        );
+        claims.jti = Some(family.current_jti);
+        claims.family_id = Some(family.id);
+        claims.scope.clone_from(&family.scope);
+        claims.resource.clone_from(&family.resource);
+
+        self.sign(&claims)
+    }
+
+    /// Creates an ID token for `user`, bound to `access_token` through the `at_hash` claim.
+    pub fn create_id_token(
+        &self,
+        family: &RefreshTokenFamily,
+        user: &User,
+        nonce: Option<String>,
+        access_token: &str,
+        expiry: chrono::Duration,
+    ) -> Result<String, InternalError> {
+        let key_ring = self.key_ring.read().expect("key ring lock poisoned");
+        let signer = key_ring.signing_key().ok_or(InternalError::NoSigningKey)?;
+
+        let iat = chrono::Utc::now().timestamp() as usize;
+        signer.sign(&IdTokenClaims {
+            iss: self.issuer.clone(),
+            sub: user.id.to_string(),
+            aud: family.client_id.clone(),
+            exp: iat + expiry.num_seconds() as usize,
+            iat,
+            auth_time: family.auth_time.timestamp() as usize,
+            nonce,
+            email: user.email.clone(),
+            email_verified: user.activated_at.is_some(),
+            preferred_username: user.username.clone(),
+            at_hash: signer.half_hash(access_token),
+            sid: family.sid.clone(),
+        })
+    }
+
+    /// Creates a logout token telling `client_id` that the session `sid` of `user_id` ended.
+    pub fn create_logout_token(
+        &self,
+        client_id: &str,
+        user_id: uuid::Uuid,
+        sid: &str,
+    ) -> Result<String, InternalError> {
+        let key_ring = self.key_ring.read().expect("key ring lock poisoned");
+        let signer = key_ring.signing_key().ok_or(InternalError::NoSigningKey)?;
+
+        let iat = chrono::Utc::now().timestamp() as usize;
+        signer.sign_with_type(
+            &LogoutTokenClaims {
+                iss: self.issuer.clone(),
+                sub: user_id.to_string(),
+                aud: client_id.to_string(),
+                iat,
+                exp: iat + 120,
+                jti: uuid::Uuid::new_v4(),
+                sid: sid.to_string(),
+                events: serde_json::json!({
+                    "http://schemas.openid.net/event/backchannel-logout": {}
+                }),
+            },
+            "logout+jwt",
+        )
     }
 
     pub fn create_activation_code(&self, user_id: uuid::Uuid) -> Result<String, InternalError> {
-        self.jwt_signer.sign(&Claims::new(
+        self.sign(&Claims::new(
             JwtType::ActivationCode,
+            self.issuer.clone(),
             "agus.dev sso".to_string(),
-            user_id,
+            user_id.to_string(),
             chrono::Duration::minutes(15),
         ))
     }
 
+    /// Verifies a `private_key_jwt` client assertion against the client's keys. Assertions are
+    /// only accepted once, so their `jti` is remembered until they expire.
+    pub async fn verify_client_assertion(
+        &self,
+        assertion: &str,
+        client_id: &str,
+        jwks: &JwkSet,
+    ) -> Result<(), JwtVerifyError> {
+        let token_endpoint = format!("{}/oauth2/token", self.issuer);
+        let claims = jwt::verify_client_assertion(
+            assertion,
+            jwks,
+            client_id,
+            &[&self.issuer, &token_endpoint],
+            Self::CLIENT_ASSERTION_ALGORITHMS,
+        )?;
+
+        if !self
+            .mark_client_assertion_as_used(&claims)
+            .await
+            .map_err(JwtVerifyError::InternalError)?
+        {
+            tracing::warn!(client_id, jti = claims.jti, "client assertion replayed");
+            return Err(JwtVerifyError::InvalidToken);
+        }
+
+        Ok(())
+    }
+
+    /// Verifies a request object signed with the client's keys and meant for this service.
+    pub fn verify_request_object(
+        &self,
+        request: &str,
+        client_id: &str,
+        jwks: &JwkSet,
+    ) -> Result<serde_json::Map<String, serde_json::Value>, JwtVerifyError> {
+        jwt::verify_request_object(
+            request,
+            jwks,
+            client_id,
+            &self.issuer,
+            Self::CLIENT_ASSERTION_ALGORITHMS,
+        )
+    }
+
+    async fn mark_client_assertion_as_used(
+        &self,
+        claims: &ClientAssertionClaims,
+    ) -> Result<bool, InternalError> {
+        let mut conn = self.kv_pool.get().await?;
+        let key = format!("client_assertion:{}:{}", claims.iss, claims.jti);
+        // expired assertions are still accepted within the validation leeway
+        let ttl = claims.exp as i64 - chrono::Utc::now().timestamp() + 60;
+
+        let result: Option<String> = conn
+            .set_options(
+                &key,
+                1,
+                SetOptions::default()
+                    .conditional_set(ExistenceCheck::NX)
+                    .with_expiration(SetExpiry::EX(ttl.max(1) as u64)),
+            )
+            .await?;
+
+        Ok(result.is_some())
+    }
+
     pub async fn mark_authorization_code_as_used(
         &self,
         token: &str,
@@ -102,3 +481,94 @@ impl TokenService {
         Ok(result.is_some())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kvs::kvs_pool;
+    use crate::services::oauth2::UserInfoError;
+    use crate::services::tokens::keys::PrivateKey;
+    use axum::response::IntoResponse;
+    use jsonwebtoken::{encode, EncodingKey, Header};
+    use serde_json::json;
+    use uuid::Uuid;
+
+    const SECRET: &[u8] = b"secret";
+
+    fn token_service() -> TokenService {
+        let kv_pool = kvs_pool("redis://127.0.0.1").unwrap();
+        TokenService::new(Arc::new(kv_pool), "https://sso.example".to_string())
+    }
+
+    /// An access token as signed before the key ring existed.
+    fn baseline_access_token(user_id: Uuid) -> String {
+        let iat = chrono::Utc::now().timestamp();
+        let claims = json!({
+            "jwt_type": "access_token",
+            "aud": "app",
+            "exp": iat + 60 * 60,
+            "iat": iat,
+            "iss": "agus.dev sso",
+            "sub": user_id,
+        });
+        encode(
+            &Header::new(Algorithm::HS256),
+            &claims,
+            &EncodingKey::from_secret(SECRET),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn baseline_tokens_are_verified_with_the_legacy_signer() {
+        let token_service = token_service().set_legacy_signer(JwtSigner::new(SECRET));
+        let user_id = Uuid::new_v4();
+
+        let claims = token_service
+            .verify_any(&baseline_access_token(user_id))
+            .unwrap();
+        assert_eq!(claims.jwt_type, JwtType::AccessToken);
+        assert_eq!(claims.user_id(), Some(user_id));
+    }
+
+    #[test]
+    fn baseline_tokens_are_rejected_without_the_legacy_signer() {
+        assert!(matches!(
+            token_service().verify_any(&baseline_access_token(Uuid::new_v4())),
+            Err(JwtVerifyError::InvalidToken)
+        ));
+    }
+
+    #[tokio::test]
+    async fn resource_server_tokens_are_rejected_by_own_endpoints() {
+        let token_service = token_service();
+        let key = PrivateKey::generate(Algorithm::EdDSA).unwrap();
+        let mut key_ring = KeyRing::default();
+        key_ring.insert(
+            JwtSigner::from_private_key(&key, Some("kid".to_string())).unwrap(),
+            chrono::Utc::now(),
+        );
+        token_service.set_key_ring(key_ring);
+
+        let token = token_service
+            .create_access_token(
+                "app".to_string(),
+                Uuid::new_v4().to_string(),
+                Some("openid".to_string()),
+                None,
+                Some("https://api.example".to_string()),
+                chrono::Duration::minutes(5),
+            )
+            .unwrap();
+
+        let error = token_service
+            .verify_own_access_token(&token)
+            .await
+            .unwrap_err();
+        let response = UserInfoError::from(error).into_response();
+        assert!(response.headers()["www-authenticate"]
+            .to_str()
+            .unwrap()
+            .contains(r#"error="invalid_token""#));
+    }
+}
@@ -1,9 +1,15 @@
 use crate::helpers::{InternalError, ManualErrorHandle, ManualErrorHandling};
-use axum::http::StatusCode;
+use crate::services::tokens::keys::{KeyError, PrivateKey};
+use axum::http::{header, StatusCode};
 use axum::response::{IntoResponse, Response};
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
 use jsonwebtoken::errors::ErrorKind;
+use jsonwebtoken::jwk::{Jwk, JwkSet};
 use jsonwebtoken::{encode, Algorithm, DecodingKey, EncodingKey, Header};
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256, Sha384, Sha512};
 use uuid::Uuid;
 
 #[derive(Debug, Serialize, Deserialize, Eq, PartialEq)]
@@ -22,11 +28,51 @@ pub struct Claims {
     pub exp: usize,
     pub iat: usize,
     pub iss: String,
-    pub sub: Uuid,
+    /// The user the token was issued for, or the client for tokens it got on its own behalf.
+    pub sub: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scope: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub nonce: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub auth_time: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub jti: Option<Uuid>,
+    /// Refresh token family the token belongs to.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub family_id: Option<Uuid>,
+    /// Redirect uri the authorization code was issued for.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub redirect_uri: Option<String>,
+    /// Whether the redirect uri was sent with the authorization request, so it must be sent again.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub redirect_uri_sent: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub code_challenge: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub code_challenge_method: Option<String>,
+    /// Session the authorization code was issued from.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sid: Option<String>,
+    /// Client an access token was issued to, as its audience is the resource server.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_id: Option<String>,
+    /// Resource indicator the authorization code was issued for.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub resource: Option<String>,
+    /// Party acting on behalf of the subject of an exchanged token.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub act: Option<Actor>,
 }
 
 impl Claims {
-    pub(super) fn new(jwt_type: JwtType, aud: String, sub: Uuid, exp: chrono::Duration) -> Self {
+    pub(super) fn new(
+        jwt_type: JwtType,
+        iss: String,
+        aud: String,
+        sub: String,
+        exp: chrono::Duration,
+    ) -> Self {
         let iat = chrono::Utc::now().timestamp() as usize;
         let exp = iat + exp.num_seconds() as usize;
 
@@ -35,55 +81,290 @@ impl Claims {
             aud,
             exp,
             iat,
-            iss: "agus.dev sso".to_string(),
+            iss,
             sub,
+            scope: None,
+            nonce: None,
+            auth_time: None,
+            jti: Some(Uuid::new_v4()),
+            family_id: None,
+            redirect_uri: None,
+            redirect_uri_sent: false,
+            code_challenge: None,
+            code_challenge_method: None,
+            sid: None,
+            client_id: None,
+            resource: None,
+            act: None,
         }
     }
+
+    /// Client the token was issued to.
+    pub fn client_id(&self) -> &str {
+        self.client_id.as_deref().unwrap_or(&self.aud)
+    }
+
+    /// User the token was issued for, `None` when the client got it on its own behalf.
+    pub fn user_id(&self) -> Option<Uuid> {
+        if self.sub == self.client_id() {
+            return None;
+        }
+
+        self.sub.parse().ok()
+    }
+}
+
+/// Actor claim (RFC 8693 section 4.1). Earlier actors of a delegation chain are nested in `act`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Actor {
+    pub sub: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub act: Option<Box<Actor>>,
+}
+
+/// Claims of an OpenID Connect ID token.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct IdTokenClaims {
+    pub iss: String,
+    pub sub: String,
+    pub aud: String,
+    pub exp: usize,
+    pub iat: usize,
+    pub auth_time: usize,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub nonce: Option<String>,
+    pub email: String,
+    pub email_verified: bool,
+    pub preferred_username: String,
+    pub at_hash: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sid: Option<String>,
+}
+
+/// Claims of a back-channel logout token (OpenID Connect Back-Channel Logout 1.0).
+#[derive(Debug, Serialize)]
+pub struct LogoutTokenClaims {
+    pub iss: String,
+    pub sub: String,
+    pub aud: String,
+    pub iat: usize,
+    pub exp: usize,
+    pub jti: Uuid,
+    pub sid: String,
+    pub events: serde_json::Value,
+}
+
+/// Claims of a client assertion (RFC 7523 section 3).
+#[derive(Debug, Deserialize)]
+pub struct ClientAssertionClaims {
+    pub iss: String,
+    pub exp: usize,
+    pub jti: String,
+}
+
+/// Subject of a JWT, read without verifying it, to find out whose keys verify it.
+pub fn unverified_subject(token: &str) -> Option<String> {
+    #[derive(Deserialize)]
+    struct Subject {
+        sub: String,
+    }
+
+    let payload = token.split('.').nth(1)?;
+    let payload = URL_SAFE_NO_PAD.decode(payload).ok()?;
+    serde_json::from_slice::<Subject>(&payload)
+        .ok()
+        .map(|subject| subject.sub)
+}
+
+/// Verifies a client assertion signed with one of the keys of `jwks`, issued by the client for
+/// one of `audience`.
+pub fn verify_client_assertion(
+    token: &str,
+    jwks: &JwkSet,
+    client_id: &str,
+    audience: &[&str],
+    algorithms: &[Algorithm],
+) -> Result<ClientAssertionClaims, JwtVerifyError> {
+    verify_with_jwks(token, jwks, algorithms, |validation| {
+        validation.set_issuer(&[client_id]);
+        validation.set_audience(audience);
+        validation.sub = Some(client_id.to_string());
+        validation.set_required_spec_claims(&["exp", "iss", "sub", "aud"]);
+    })
+}
+
+/// Verifies a request object (RFC 9101) signed by the client with one of the keys of `jwks`,
+/// returning its claims, the parameters of the authorization request.
+pub fn verify_request_object(
+    token: &str,
+    jwks: &JwkSet,
+    client_id: &str,
+    audience: &str,
+    algorithms: &[Algorithm],
+) -> Result<serde_json::Map<String, serde_json::Value>, JwtVerifyError> {
+    verify_with_jwks(token, jwks, algorithms, |validation| {
+        validation.set_issuer(&[client_id]);
+        validation.set_audience(&[audience]);
+        validation.set_required_spec_claims(&["exp", "iss", "aud"]);
+    })
 }
 
-pub(super) struct JwtSigner {
+/// Verifies a token signed by a third party with one of the keys of `jwks`, using one of
+/// `algorithms`.
+fn verify_with_jwks<T: DeserializeOwned>(
+    token: &str,
+    jwks: &JwkSet,
+    algorithms: &[Algorithm],
+    configure: impl FnOnce(&mut jsonwebtoken::Validation),
+) -> Result<T, JwtVerifyError> {
+    let header = jsonwebtoken::decode_header(token).map_err(|_| JwtVerifyError::InvalidToken)?;
+    if !algorithms.contains(&header.alg) {
+        return Err(JwtVerifyError::InvalidToken);
+    }
+
+    let mut validation = jsonwebtoken::Validation::new(header.alg);
+    configure(&mut validation);
+
+    jwks.keys
+        .iter()
+        .filter(|jwk| header.kid.is_none() || jwk.common.key_id == header.kid)
+        .filter_map(|jwk| DecodingKey::from_jwk(jwk).ok())
+        .find_map(|key| jsonwebtoken::decode::<T>(token, &key, &validation).ok())
+        .map(|token_data| token_data.claims)
+        .ok_or(JwtVerifyError::InvalidToken)
+}
+
+pub struct JwtSigner {
+    kid: Option<String>,
+    algorithm: Algorithm,
     encoding_key: EncodingKey,
     decoding_key: DecodingKey,
+    public_jwk: Option<Jwk>,
 }
 
 impl JwtSigner {
-    pub(super) fn new(secret: &[u8]) -> Self {
+    pub fn new(secret: &[u8]) -> Self {
         Self {
+            kid: None,
+            algorithm: Algorithm::HS256,
             encoding_key: EncodingKey::from_secret(secret),
             decoding_key: DecodingKey::from_secret(secret),
+            public_jwk: None,
         }
     }
 
-    pub(super) fn sign(&self, claims: &Claims) -> Result<String, InternalError> {
-        let header = Header::new(Algorithm::HS256);
+    /// Creates a signer for an asymmetric key. The `kid` defaults to the key's JWK thumbprint.
+    pub fn from_private_key(key: &PrivateKey, kid: Option<String>) -> Result<Self, KeyError> {
+        let kid = kid.unwrap_or_else(|| key.thumbprint());
+        let public_jwk = key.public_jwk(kid.clone());
+
+        Ok(Self {
+            kid: Some(kid),
+            algorithm: key.algorithm(),
+            encoding_key: key.encoding_key()?,
+            decoding_key: DecodingKey::from_jwk(&public_jwk)
+                .map_err(|e| KeyError::Encoding(e.to_string()))?,
+            public_jwk: Some(public_jwk),
+        })
+    }
+
+    pub(super) fn kid(&self) -> Option<&str> {
+        self.kid.as_deref()
+    }
+
+    pub(super) fn algorithm(&self) -> Algorithm {
+        self.algorithm
+    }
+
+    /// Left-most half of the hash of `token`, using the hash function of the signing algorithm,
+    /// as used by the `at_hash` claim.
+    pub(super) fn half_hash(&self, token: &str) -> String {
+        let digest = match self.algorithm {
+            Algorithm::HS384 | Algorithm::RS384 | Algorithm::PS384 | Algorithm::ES384 => {
+                Sha384::digest(token.as_bytes()).to_vec()
+            }
+            Algorithm::HS512 | Algorithm::RS512 | Algorithm::PS512 | Algorithm::EdDSA => {
+                Sha512::digest(token.as_bytes()).to_vec()
+            }
+            _ => Sha256::digest(token.as_bytes()).to_vec(),
+        };
+
+        URL_SAFE_NO_PAD.encode(&digest[..digest.len() / 2])
+    }
+
+    pub(super) fn public_jwk(&self) -> Option<&Jwk> {
+        self.public_jwk.as_ref()
+    }
+
+    pub(super) fn sign<T: Serialize>(&self, claims: &T) -> Result<String, InternalError> {
+        self.sign_with_type(claims, "JWT")
+    }
+
+    /// Signs `claims` with an explicit `typ` header, for tokens that must not be mistaken for
+    /// other JWTs.
+    pub(super) fn sign_with_type<T: Serialize>(
+        &self,
+        claims: &T,
+        typ: &str,
+    ) -> Result<String, InternalError> {
+        let mut header = Header::new(self.algorithm);
+        header.typ = Some(typ.to_string());
+        header.kid.clone_from(&self.kid);
         let token = encode(&header, claims, &self.encoding_key)?;
         Ok(token)
     }
 
-    pub(super) fn verify(&self, token: &str) -> Result<Claims, JwtVerifyError> {
-        let mut validation = jsonwebtoken::Validation::new(Algorithm::HS256);
+    pub(super) fn verify(&self, token: &str, issuers: &[&str]) -> Result<Claims, JwtVerifyError> {
+        self.decode(token, issuers, true)
+    }
+
+    /// Unlike other tokens, ID tokens are still accepted once expired.
+    pub(super) fn verify_id_token(
+        &self,
+        token: &str,
+        issuers: &[&str],
+    ) -> Result<IdTokenClaims, JwtVerifyError> {
+        self.decode(token, issuers, false)
+    }
+
+    fn decode<T: DeserializeOwned>(
+        &self,
+        token: &str,
+        issuers: &[&str],
+        validate_exp: bool,
+    ) -> Result<T, JwtVerifyError> {
+        let mut validation = jsonwebtoken::Validation::new(self.algorithm);
         validation.validate_aud = false;
-        validation.validate_exp = true;
-        validation.set_issuer::<&str>(&["agus.dev sso"]);
+        validation.validate_exp = validate_exp;
+        validation.set_issuer(issuers);
 
-        let token_data = jsonwebtoken::decode::<Claims>(token, &self.decoding_key, &validation)
+        let token_data = jsonwebtoken::decode::<T>(token, &self.decoding_key, &validation)
             .manual_error_handling()?;
 
         Ok(token_data.claims)
     }
 }
 
+/// Rejects a bearer token with a `WWW-Authenticate` challenge (RFC 6750 section 3).
 impl IntoResponse for JwtVerifyError {
     fn into_response(self) -> Response {
-        match self {
-            JwtVerifyError::InvalidToken => {
-                (StatusCode::UNAUTHORIZED, "invalid token").into_response()
-            }
-            JwtVerifyError::ExpiredToken => {
-                (StatusCode::UNAUTHORIZED, "expired token").into_response()
-            }
-            JwtVerifyError::InternalError(e) => e.into_response(),
-        }
+        let description = match self {
+            JwtVerifyError::InvalidToken => "invalid token",
+            JwtVerifyError::ExpiredToken => "expired token",
+            JwtVerifyError::InternalError(e) => return e.into_response(),
+        };
+        let challenge = format!(
+            r#"Bearer error="invalid_token", error_description="{}""#,
+            description
+        );
+
+        (
+            StatusCode::UNAUTHORIZED,
+            [(header::WWW_AUTHENTICATE, challenge)],
+        )
+            .into_response()
     }
 }
 
@@ -108,7 +389,105 @@ impl From<ManualErrorHandling<jsonwebtoken::errors::Error>> for JwtVerifyError {
             ErrorKind::InvalidAudience => Self::InvalidToken,
             ErrorKind::InvalidSubject => Self::InvalidToken,
             ErrorKind::ImmatureSignature => Self::InvalidToken,
+            // tokens of another shape or algorithm than expected, like ID tokens or forgeries
+            ErrorKind::InvalidAlgorithm => Self::InvalidToken,
+            ErrorKind::MissingAlgorithm => Self::InvalidToken,
+            ErrorKind::MissingRequiredClaim(_) => Self::InvalidToken,
+            ErrorKind::Base64(_) => Self::InvalidToken,
+            ErrorKind::Json(_) => Self::InvalidToken,
+            ErrorKind::Utf8(_) => Self::InvalidToken,
             _ => Self::InternalError(error.into()),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    const ALGORITHMS: &[Algorithm] = &[Algorithm::ES256];
+
+    fn sign_assertion(key: &PrivateKey, claims: serde_json::Value) -> String {
+        let mut header = Header::new(key.algorithm());
+        header.kid = Some("key".to_string());
+        encode(&header, &claims, &key.encoding_key().unwrap()).unwrap()
+    }
+
+    fn assertion_claims(client_id: &str, audience: &str) -> serde_json::Value {
+        json!({
+            "iss": client_id,
+            "sub": client_id,
+            "aud": audience,
+            "exp": chrono::Utc::now().timestamp() + 60,
+            "jti": Uuid::new_v4().to_string(),
+        })
+    }
+
+    fn jwks(key: &PrivateKey) -> JwkSet {
+        JwkSet {
+            keys: vec![key.public_jwk("key".to_string())],
+        }
+    }
+
+    #[test]
+    fn client_assertion_is_verified() {
+        let key = PrivateKey::generate(Algorithm::ES256).unwrap();
+        let assertion = sign_assertion(&key, assertion_claims("app", "https://sso/oauth2/token"));
+
+        let claims = verify_client_assertion(
+            &assertion,
+            &jwks(&key),
+            "app",
+            &["https://sso", "https://sso/oauth2/token"],
+            ALGORITHMS,
+        )
+        .unwrap();
+        assert_eq!(claims.iss, "app");
+    }
+
+    #[test]
+    fn client_assertion_of_another_client_is_rejected() {
+        let key = PrivateKey::generate(Algorithm::ES256).unwrap();
+        let assertion = sign_assertion(&key, assertion_claims("other", "https://sso"));
+
+        assert!(matches!(
+            verify_client_assertion(&assertion, &jwks(&key), "app", &["https://sso"], ALGORITHMS),
+            Err(JwtVerifyError::InvalidToken)
+        ));
+    }
+
+    #[test]
+    fn client_assertion_for_another_audience_is_rejected() {
+        let key = PrivateKey::generate(Algorithm::ES256).unwrap();
+        let assertion = sign_assertion(&key, assertion_claims("app", "https://elsewhere"));
+
+        assert!(matches!(
+            verify_client_assertion(&assertion, &jwks(&key), "app", &["https://sso"], ALGORITHMS),
+            Err(JwtVerifyError::InvalidToken)
+        ));
+    }
+
+    #[test]
+    fn client_assertion_signed_with_another_key_is_rejected() {
+        let key = PrivateKey::generate(Algorithm::ES256).unwrap();
+        let other_key = PrivateKey::generate(Algorithm::ES256).unwrap();
+        let assertion = sign_assertion(&other_key, assertion_claims("app", "https://sso"));
+
+        assert!(matches!(
+            verify_client_assertion(&assertion, &jwks(&key), "app", &["https://sso"], ALGORITHMS),
+            Err(JwtVerifyError::InvalidToken)
+        ));
+    }
+
+    #[test]
+    fn client_assertion_with_unexpected_algorithm_is_rejected() {
+        let key = PrivateKey::generate(Algorithm::EdDSA).unwrap();
+        let assertion = sign_assertion(&key, assertion_claims("app", "https://sso"));
+
+        assert!(matches!(
+            verify_client_assertion(&assertion, &jwks(&key), "app", &["https://sso"], ALGORITHMS),
+            Err(JwtVerifyError::InvalidToken)
+        ));
+    }
+}
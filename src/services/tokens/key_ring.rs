@@ -0,0 +1,65 @@
+use crate::services::tokens::jwt::JwtSigner;
+use chrono::{DateTime, Utc};
+use jsonwebtoken::jwk::JwkSet;
+use jsonwebtoken::Algorithm;
+
+/// Set of asymmetric keys known to the token service.
+///
+/// Every key in the ring can verify tokens and is published in the JWKS, including keys that are
+/// scheduled but not yet active. Only the most recently activated key signs new tokens.
+#[derive(Default)]
+pub struct KeyRing {
+    keys: Vec<RingKey>,
+}
+
+struct RingKey {
+    signer: JwtSigner,
+    activates_at: DateTime<Utc>,
+}
+
+impl KeyRing {
+    pub fn insert(&mut self, signer: JwtSigner, activates_at: DateTime<Utc>) {
+        self.keys.push(RingKey {
+            signer,
+            activates_at,
+        });
+    }
+
+    pub(super) fn signing_key(&self) -> Option<&JwtSigner> {
+        let now = Utc::now();
+        self.keys
+            .iter()
+            .filter(|key| key.activates_at <= now)
+            .max_by_key(|key| key.activates_at)
+            .map(|key| &key.signer)
+    }
+
+    pub(super) fn find(&self, kid: &str) -> Option<&JwtSigner> {
+        self.keys
+            .iter()
+            .map(|key| &key.signer)
+            .find(|signer| signer.kid() == Some(kid))
+    }
+
+    pub(super) fn algorithms(&self) -> Vec<Algorithm> {
+        let mut algorithms: Vec<Algorithm> = Vec::new();
+        for key in &self.keys {
+            if !algorithms.contains(&key.signer.algorithm()) {
+                algorithms.push(key.signer.algorithm());
+            }
+        }
+
+        algorithms
+    }
+
+    pub(super) fn jwks(&self) -> JwkSet {
+        JwkSet {
+            keys: self
+                .keys
+                .iter()
+                .filter_map(|key| key.signer.public_jwk())
+                .cloned()
+                .collect(),
+        }
+    }
+}
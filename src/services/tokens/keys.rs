@@ -0,0 +1,243 @@
+use argon2::password_hash::rand_core::OsRng;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use jsonwebtoken::jwk::{
+    AlgorithmParameters, CommonParameters, EllipticCurve, EllipticCurveKeyParameters,
+    EllipticCurveKeyType, Jwk, KeyAlgorithm, OctetKeyPairParameters, OctetKeyPairType,
+    PublicKeyUse, RSAKeyParameters, RSAKeyType,
+};
+use jsonwebtoken::{Algorithm, EncodingKey};
+use p256::elliptic_curve::sec1::ToEncodedPoint;
+use rsa::pkcs1::{DecodeRsaPrivateKey, EncodeRsaPrivateKey};
+use rsa::pkcs8::{DecodePrivateKey, EncodePrivateKey, LineEnding};
+use rsa::traits::PublicKeyParts;
+use rsa::{BigUint, RsaPrivateKey};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+
+/// Private half of an asymmetric signing key.
+pub enum PrivateKey {
+    Rsa(RsaPrivateKey),
+    Ec(p256::SecretKey),
+    Ed25519(ed25519_dalek::SigningKey),
+}
+
+#[derive(Deserialize)]
+struct PrivateJwk {
+    kty: String,
+    crv: Option<String>,
+    kid: Option<String>,
+    n: Option<String>,
+    e: Option<String>,
+    d: Option<String>,
+    p: Option<String>,
+    q: Option<String>,
+}
+
+impl PrivateKey {
+    pub fn generate(algorithm: Algorithm) -> Result<Self, KeyError> {
+        let key = match algorithm {
+            Algorithm::RS256 => Self::Rsa(
+                RsaPrivateKey::new(&mut OsRng, 2048)
+                    .map_err(|e| KeyError::Encoding(e.to_string()))?,
+            ),
+            Algorithm::ES256 => Self::Ec(p256::SecretKey::random(&mut OsRng)),
+            Algorithm::EdDSA => Self::Ed25519(ed25519_dalek::SigningKey::generate(&mut OsRng)),
+            _ => return Err(KeyError::UnsupportedKey),
+        };
+
+        Ok(key)
+    }
+
+    /// Reads a key from a PEM (PKCS#1, SEC1 or PKCS#8) or JWK file. Returns the `kid` of the key
+    /// as well when the file carries one.
+    pub fn read_file(path: &str) -> Result<(Self, Option<String>), KeyError> {
+        let content = std::fs::read_to_string(path)?;
+        if content.trim_start().starts_with('{') {
+            Self::from_jwk(&content)
+        } else {
+            Ok((Self::from_pem(&content)?, None))
+        }
+    }
+
+    pub fn from_pem(pem: &str) -> Result<Self, KeyError> {
+        if let Ok(key) = RsaPrivateKey::from_pkcs8_pem(pem) {
+            return Ok(Self::Rsa(key));
+        }
+        if let Ok(key) = RsaPrivateKey::from_pkcs1_pem(pem) {
+            return Ok(Self::Rsa(key));
+        }
+        if let Ok(key) = p256::SecretKey::from_pkcs8_pem(pem) {
+            return Ok(Self::Ec(key));
+        }
+        if let Ok(key) = p256::SecretKey::from_sec1_pem(pem) {
+            return Ok(Self::Ec(key));
+        }
+        if let Ok(key) = ed25519_dalek::SigningKey::from_pkcs8_pem(pem) {
+            return Ok(Self::Ed25519(key));
+        }
+
+        Err(KeyError::UnsupportedKey)
+    }
+
+    pub fn from_jwk(jwk: &str) -> Result<(Self, Option<String>), KeyError> {
+        let jwk: PrivateJwk = serde_json::from_str(jwk)?;
+        let param = |value: &Option<String>, name: &'static str| {
+            value
+                .as_deref()
+                .ok_or(KeyError::InvalidJwk(name))
+                .and_then(|value| {
+                    URL_SAFE_NO_PAD
+                        .decode(value)
+                        .map_err(|_| KeyError::InvalidJwk(name))
+                })
+        };
+
+        let key = match (jwk.kty.as_str(), jwk.crv.as_deref()) {
+            ("RSA", _) => {
+                let n = BigUint::from_bytes_be(&param(&jwk.n, "n")?);
+                let e = BigUint::from_bytes_be(&param(&jwk.e, "e")?);
+                let d = BigUint::from_bytes_be(&param(&jwk.d, "d")?);
+                let primes = vec![
+                    BigUint::from_bytes_be(&param(&jwk.p, "p")?),
+                    BigUint::from_bytes_be(&param(&jwk.q, "q")?),
+                ];
+                Self::Rsa(
+                    RsaPrivateKey::from_components(n, e, d, primes)
+                        .map_err(|_| KeyError::InvalidJwk("RSA"))?,
+                )
+            }
+            ("EC", Some("P-256")) => Self::Ec(
+                p256::SecretKey::from_slice(&param(&jwk.d, "d")?)
+                    .map_err(|_| KeyError::InvalidJwk("d"))?,
+            ),
+            ("OKP", Some("Ed25519")) => Self::Ed25519(ed25519_dalek::SigningKey::from_bytes(
+                &param(&jwk.d, "d")?
+                    .try_into()
+                    .map_err(|_| KeyError::InvalidJwk("d"))?,
+            )),
+            _ => return Err(KeyError::UnsupportedKey),
+        };
+
+        Ok((key, jwk.kid))
+    }
+
+    /// Serializes the key as a PKCS#8 PEM document.
+    pub fn to_pem(&self) -> Result<String, KeyError> {
+        let pem = match self {
+            Self::Rsa(key) => key.to_pkcs8_pem(LineEnding::LF)?,
+            Self::Ec(key) => key.to_pkcs8_pem(LineEnding::LF)?,
+            Self::Ed25519(key) => key.to_pkcs8_pem(LineEnding::LF)?,
+        };
+
+        Ok(pem.to_string())
+    }
+
+    pub fn algorithm(&self) -> Algorithm {
+        match self {
+            Self::Rsa(_) => Algorithm::RS256,
+            Self::Ec(_) => Algorithm::ES256,
+            Self::Ed25519(_) => Algorithm::EdDSA,
+        }
+    }
+
+    pub fn encoding_key(&self) -> Result<EncodingKey, KeyError> {
+        let key = match self {
+            Self::Rsa(key) => EncodingKey::from_rsa_der(key.to_pkcs1_der()?.as_bytes()),
+            Self::Ec(key) => EncodingKey::from_ec_der(key.to_pkcs8_der()?.as_bytes()),
+            Self::Ed25519(key) => EncodingKey::from_ed_der(key.to_pkcs8_der()?.as_bytes()),
+        };
+
+        Ok(key)
+    }
+
+    /// Public half of the key, as published in the JWKS.
+    pub fn public_jwk(&self, kid: String) -> Jwk {
+        let (key_algorithm, algorithm) = match self {
+            Self::Rsa(key) => (
+                KeyAlgorithm::RS256,
+                AlgorithmParameters::RSA(RSAKeyParameters {
+                    key_type: RSAKeyType::RSA,
+                    n: URL_SAFE_NO_PAD.encode(key.n().to_bytes_be()),
+                    e: URL_SAFE_NO_PAD.encode(key.e().to_bytes_be()),
+                }),
+            ),
+            Self::Ec(key) => {
+                let point = key.public_key().to_encoded_point(false);
+                (
+                    KeyAlgorithm::ES256,
+                    AlgorithmParameters::EllipticCurve(EllipticCurveKeyParameters {
+                        key_type: EllipticCurveKeyType::EC,
+                        curve: EllipticCurve::P256,
+                        x: URL_SAFE_NO_PAD.encode(point.x().expect("uncompressed point")),
+                        y: URL_SAFE_NO_PAD.encode(point.y().expect("uncompressed point")),
+                    }),
+                )
+            }
+            Self::Ed25519(key) => (
+                KeyAlgorithm::EdDSA,
+                AlgorithmParameters::OctetKeyPair(OctetKeyPairParameters {
+                    key_type: OctetKeyPairType::OctetKeyPair,
+                    curve: EllipticCurve::Ed25519,
+                    x: URL_SAFE_NO_PAD.encode(key.verifying_key().as_bytes()),
+                }),
+            ),
+        };
+
+        Jwk {
+            common: CommonParameters {
+                public_key_use: Some(PublicKeyUse::Signature),
+                key_algorithm: Some(key_algorithm),
+                key_id: Some(kid),
+                ..Default::default()
+            },
+            algorithm,
+        }
+    }
+
+    /// JWK thumbprint of the public key (RFC 7638), used as the default `kid`.
+    pub fn thumbprint(&self) -> String {
+        // members must be in lexicographic order with no whitespace
+        let canonical = match self.public_jwk(String::new()).algorithm {
+            AlgorithmParameters::RSA(params) => {
+                format!(r#"{{"e":"{}","kty":"RSA","n":"{}"}}"#, params.e, params.n)
+            }
+            AlgorithmParameters::EllipticCurve(params) => format!(
+                r#"{{"crv":"P-256","kty":"EC","x":"{}","y":"{}"}}"#,
+                params.x, params.y
+            ),
+            AlgorithmParameters::OctetKeyPair(params) => {
+                format!(r#"{{"crv":"Ed25519","kty":"OKP","x":"{}"}}"#, params.x)
+            }
+            AlgorithmParameters::OctetKey(_) => unreachable!("private keys are asymmetric"),
+        };
+
+        URL_SAFE_NO_PAD.encode(Sha256::digest(canonical.as_bytes()))
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum KeyError {
+    #[error("unsupported key type")]
+    UnsupportedKey,
+    #[error("invalid JWK parameter: {0}")]
+    InvalidJwk(&'static str),
+    #[error("malformed JWK: {0}")]
+    MalformedJwk(#[from] serde_json::Error),
+    #[error("key encoding error: {0}")]
+    Encoding(String),
+    #[error("io error: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+impl From<rsa::pkcs1::Error> for KeyError {
+    fn from(err: rsa::pkcs1::Error) -> Self {
+        KeyError::Encoding(err.to_string())
+    }
+}
+
+impl From<rsa::pkcs8::Error> for KeyError {
+    fn from(err: rsa::pkcs8::Error) -> Self {
+        KeyError::Encoding(err.to_string())
+    }
+}